@@ -0,0 +1,24 @@
+-- Per-game content manifests used by clients to verify downloads
+CREATE TABLE game_manifests (
+                                game_id TEXT PRIMARY KEY,
+                                status TEXT NOT NULL DEFAULT 'pending', -- pending, ready, failed
+                                file_count INTEGER NOT NULL DEFAULT 0,
+                                total_size INTEGER NOT NULL DEFAULT 0,
+                                error TEXT,
+                                generated_at DATETIME,
+                                updated_at DATETIME NOT NULL,
+                                FOREIGN KEY (game_id) REFERENCES games(id) ON DELETE CASCADE
+);
+
+CREATE TABLE game_files (
+                            id TEXT PRIMARY KEY,
+                            game_id TEXT NOT NULL,
+                            relative_path TEXT NOT NULL,
+                            size INTEGER NOT NULL,
+                            sha256 TEXT NOT NULL,
+                            modified_at DATETIME,
+                            FOREIGN KEY (game_id) REFERENCES games(id) ON DELETE CASCADE,
+                            UNIQUE(game_id, relative_path)
+);
+
+CREATE INDEX idx_game_files_game_id ON game_files(game_id);
@@ -0,0 +1,17 @@
+-- Versioned builds per game; games.current_build_id points at the one served to users
+CREATE TABLE game_builds (
+                             id TEXT PRIMARY KEY,
+                             game_id TEXT NOT NULL,
+                             version TEXT NOT NULL,
+                             release_notes TEXT,
+                             file_path TEXT NOT NULL,
+                             file_size INTEGER,
+                             created_at DATETIME NOT NULL,
+                             FOREIGN KEY (game_id) REFERENCES games(id) ON DELETE CASCADE,
+                             UNIQUE(game_id, version)
+);
+
+CREATE INDEX idx_game_builds_game_id ON game_builds(game_id);
+
+ALTER TABLE games ADD COLUMN current_build_id TEXT REFERENCES game_builds(id) ON DELETE SET NULL;
+ALTER TABLE user_games ADD COLUMN installed_build_id TEXT REFERENCES game_builds(id) ON DELETE SET NULL;
@@ -0,0 +1,49 @@
+-- Full-text search index over the catalog.
+-- Standalone (not external-content) so it does not depend on games' implicit rowid surviving VACUUM.
+CREATE VIRTUAL TABLE games_fts USING fts5(
+    game_id UNINDEXED,
+    name,
+    summary,
+    storyline,
+    developer,
+    publisher,
+    genres,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+-- genres is stored as a JSON array of {id, name}; only the names are worth indexing
+INSERT INTO games_fts (game_id, name, summary, storyline, developer, publisher, genres)
+SELECT
+    id, name, summary, storyline, developer, publisher,
+    CASE WHEN json_valid(genres)
+        THEN (SELECT group_concat(json_extract(value, '$.name'), ', ') FROM json_each(genres))
+        ELSE genres
+    END
+FROM games;
+
+CREATE TRIGGER games_fts_insert AFTER INSERT ON games BEGIN
+    INSERT INTO games_fts (game_id, name, summary, storyline, developer, publisher, genres)
+    VALUES (
+        new.id, new.name, new.summary, new.storyline, new.developer, new.publisher,
+        CASE WHEN json_valid(new.genres)
+            THEN (SELECT group_concat(json_extract(value, '$.name'), ', ') FROM json_each(new.genres))
+            ELSE new.genres
+        END
+    );
+END;
+
+CREATE TRIGGER games_fts_update AFTER UPDATE OF name, summary, storyline, developer, publisher, genres ON games BEGIN
+    DELETE FROM games_fts WHERE game_id = old.id;
+    INSERT INTO games_fts (game_id, name, summary, storyline, developer, publisher, genres)
+    VALUES (
+        new.id, new.name, new.summary, new.storyline, new.developer, new.publisher,
+        CASE WHEN json_valid(new.genres)
+            THEN (SELECT group_concat(json_extract(value, '$.name'), ', ') FROM json_each(new.genres))
+            ELSE new.genres
+        END
+    );
+END;
+
+CREATE TRIGGER games_fts_delete AFTER DELETE ON games BEGIN
+    DELETE FROM games_fts WHERE game_id = old.id;
+END;
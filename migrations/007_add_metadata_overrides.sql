@@ -0,0 +1,2 @@
+-- Fields an admin edited by hand (JSON array of column names); metadata refreshes leave them alone
+ALTER TABLE games ADD COLUMN metadata_overrides TEXT;
@@ -0,0 +1,5 @@
+-- Per-game metadata provider choice and that provider's id for the game
+ALTER TABLE games ADD COLUMN metadata_provider TEXT;
+ALTER TABLE games ADD COLUMN metadata_id TEXT;
+
+UPDATE games SET metadata_provider = 'igdb', metadata_id = CAST(igdb_id AS TEXT) WHERE igdb_id IS NOT NULL;
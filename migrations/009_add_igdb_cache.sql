@@ -0,0 +1,6 @@
+-- Raw IGDB responses keyed by normalized search query or game id
+CREATE TABLE IF NOT EXISTS igdb_cache (
+    cache_key TEXT PRIMARY KEY,
+    response TEXT NOT NULL,
+    fetched_at DATETIME NOT NULL
+);
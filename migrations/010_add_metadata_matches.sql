@@ -0,0 +1,21 @@
+-- Candidate metadata matches for games without a provider link.
+-- status: pending (awaiting review), accepted, rejected, auto (linked without review)
+CREATE TABLE IF NOT EXISTS metadata_matches (
+    id TEXT PRIMARY KEY,
+    game_id TEXT NOT NULL,
+    provider TEXT NOT NULL,
+    external_id TEXT NOT NULL,
+    candidate_name TEXT NOT NULL,
+    release_year INTEGER,
+    score REAL NOT NULL,
+    metadata TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    resolved_by TEXT,
+    created_at DATETIME NOT NULL,
+    resolved_at DATETIME,
+    FOREIGN KEY (game_id) REFERENCES games(id) ON DELETE CASCADE,
+    FOREIGN KEY (resolved_by) REFERENCES users(id) ON DELETE SET NULL,
+    UNIQUE(game_id, provider, external_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_metadata_matches_status ON metadata_matches(status);
@@ -0,0 +1,17 @@
+-- When a game's metadata was last pulled from its provider, so stale games can be refreshed
+ALTER TABLE games ADD COLUMN metadata_updated_at DATETIME;
+
+UPDATE games SET metadata_updated_at = updated_at
+WHERE metadata_id IS NOT NULL OR igdb_id IS NOT NULL;
+
+-- Last run of each recurring maintenance task.
+-- last_status: running, succeeded, failed
+CREATE TABLE IF NOT EXISTS scheduled_tasks (
+    name TEXT PRIMARY KEY,
+    last_status TEXT,
+    last_message TEXT,
+    last_started_at DATETIME,
+    last_finished_at DATETIME,
+    next_run_at DATETIME,
+    updated_at DATETIME NOT NULL
+);
@@ -0,0 +1,18 @@
+-- A session is a login on one device. Its access token (sessions.token) is short-lived and
+-- replaced on every refresh; refresh_expires_at is when the whole session ends if unused.
+ALTER TABLE sessions ADD COLUMN refresh_expires_at DATETIME;
+
+UPDATE sessions SET refresh_expires_at = expires_at;
+
+-- Refresh tokens rotate on every use. Used ones are kept so that presenting one again
+-- (a stolen copy) can be detected and the session revoked.
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+    id TEXT PRIMARY KEY,
+    session_id TEXT NOT NULL,
+    token_hash TEXT UNIQUE NOT NULL,
+    created_at DATETIME NOT NULL,
+    used_at DATETIME,
+    FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_session_id ON refresh_tokens(session_id);
@@ -0,0 +1,10 @@
+-- Failed login counters used to slow down and lock out password guessing.
+-- scope: user (key is the lowercased username, known or not) or ip
+CREATE TABLE IF NOT EXISTS login_failures (
+    scope TEXT NOT NULL,
+    key TEXT NOT NULL,
+    failures INTEGER NOT NULL DEFAULT 0,
+    last_failed_at DATETIME NOT NULL,
+    blocked_until DATETIME,
+    PRIMARY KEY (scope, key)
+);
@@ -0,0 +1,5 @@
+-- Admin-issued temporary passwords must be replaced at the next login and stop working
+-- after password_expires_at
+ALTER TABLE users ADD COLUMN must_change_password BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE users ADD COLUMN password_expires_at DATETIME;
+ALTER TABLE users ADD COLUMN password_changed_at DATETIME;
@@ -0,0 +1,41 @@
+-- Roles group permissions (see src/permissions.rs) and are assigned to users.
+-- Built-in roles cannot be changed or deleted. The superuser role has every permission
+-- without listing them, and users.is_admin is kept in step with membership in it.
+CREATE TABLE IF NOT EXISTS roles (
+    id TEXT PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL,
+    description TEXT,
+    built_in BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at DATETIME NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS role_permissions (
+    role_id TEXT NOT NULL,
+    permission TEXT NOT NULL,
+    PRIMARY KEY (role_id, permission),
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_id TEXT NOT NULL,
+    role_id TEXT NOT NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (user_id, role_id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_roles_role_id ON user_roles(role_id);
+
+INSERT INTO roles (id, name, description, built_in, created_at) VALUES
+    ('superuser', 'superuser', 'Full access to everything', TRUE, datetime('now')),
+    ('curator', 'curator', 'Edits games and their metadata', TRUE, datetime('now')),
+    ('uploader', 'uploader', 'Adds games and builds', TRUE, datetime('now'));
+
+INSERT INTO role_permissions (role_id, permission) VALUES
+    ('curator', 'games:edit'),
+    ('curator', 'metadata:edit'),
+    ('uploader', 'games:upload');
+
+INSERT INTO user_roles (user_id, role_id, created_at)
+SELECT id, 'superuser', datetime('now') FROM users WHERE is_admin;
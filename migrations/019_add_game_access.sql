@@ -0,0 +1,40 @@
+-- Groups of users that access rules can target
+CREATE TABLE IF NOT EXISTS user_groups (
+    id TEXT PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL,
+    description TEXT,
+    created_at DATETIME NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS user_group_members (
+    group_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    added_at DATETIME NOT NULL,
+    PRIMARY KEY (group_id, user_id),
+    FOREIGN KEY (group_id) REFERENCES user_groups(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_group_members_user_id ON user_group_members(user_id);
+
+-- Allow or deny a user or group one game, or every game under a library root.
+-- The most specific matching rule wins (user over group, then game over root, then deny
+-- over allow); games no rule matches fall back to GAME_ACCESS_DEFAULT.
+CREATE TABLE IF NOT EXISTS game_access_rules (
+    id TEXT PRIMARY KEY,
+    user_id TEXT,
+    group_id TEXT,
+    game_id TEXT,
+    library_root TEXT,
+    effect TEXT NOT NULL CHECK (effect IN ('allow', 'deny')),
+    created_by TEXT,
+    created_at DATETIME NOT NULL,
+    CHECK ((user_id IS NULL) != (group_id IS NULL)),
+    CHECK ((game_id IS NULL) != (library_root IS NULL)),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (group_id) REFERENCES user_groups(id) ON DELETE CASCADE,
+    FOREIGN KEY (game_id) REFERENCES games(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_game_access_rules_user_id ON game_access_rules(user_id);
+CREATE INDEX IF NOT EXISTS idx_game_access_rules_group_id ON game_access_rules(group_id);
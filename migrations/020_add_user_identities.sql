@@ -0,0 +1,27 @@
+-- Accounts at an OpenID Connect provider, identified by issuer and subject, that sign in
+-- as a local user. Users created on first sign-in have password_hash '!' (no password).
+CREATE TABLE IF NOT EXISTS user_identities (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    email TEXT,
+    created_at DATETIME NOT NULL,
+    last_login_at DATETIME,
+    UNIQUE (issuer, subject),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_identities_user_id ON user_identities(user_id);
+
+-- In-flight sign-ins between the redirect to the provider and the callback.
+-- link_user_id is set when a signed-in user is linking a new identity.
+CREATE TABLE IF NOT EXISTS oidc_login_states (
+    state TEXT PRIMARY KEY,
+    nonce TEXT NOT NULL,
+    code_verifier TEXT NOT NULL,
+    link_user_id TEXT,
+    redirect_to TEXT NOT NULL,
+    expires_at DATETIME NOT NULL,
+    FOREIGN KEY (link_user_id) REFERENCES users(id) ON DELETE CASCADE
+);
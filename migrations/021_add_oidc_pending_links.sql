@@ -0,0 +1,11 @@
+-- Provider accounts a signed-in user has authenticated as but not yet confirmed linking.
+-- The link is only made when the same user confirms it from their session.
+CREATE TABLE IF NOT EXISTS oidc_pending_links (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    email TEXT,
+    expires_at DATETIME NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
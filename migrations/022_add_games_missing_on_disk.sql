@@ -0,0 +1,3 @@
+-- Set by the library scanner when it hides a game whose files vanished, so it only brings
+-- back games it hid itself and never undoes an admin marking a game unavailable
+ALTER TABLE games ADD COLUMN missing_on_disk BOOLEAN NOT NULL DEFAULT FALSE;
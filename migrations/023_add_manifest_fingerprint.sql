@@ -0,0 +1,3 @@
+-- Digest of every file's path, size and mtime when the manifest was generated; the library
+-- scanner compares it with the disk to requeue manifests whose files changed in place
+ALTER TABLE game_manifests ADD COLUMN fingerprint TEXT;
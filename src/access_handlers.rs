@@ -0,0 +1,216 @@
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use crate::{
+    auth::User,
+    auth_handlers::invalid_input,
+    handlers::{AppState, ApiResponse},
+    models::{AccessRule, AccessRuleQuery, CreateAccessRuleRequest, CreateGroupRequest, GroupMember, UserGroup},
+    permissions::{ManageUsers, RequirePermission},
+};
+
+const MAX_GROUP_NAME_LEN: usize = 50;
+
+pub async fn list_groups(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageUsers>,
+) -> Result<Json<ApiResponse<Vec<UserGroup>>>, StatusCode> {
+    match state.db.get_groups().await {
+        Ok(groups) => Ok(Json(ApiResponse::success(groups))),
+        Err(e) => {
+            tracing::error!("Failed to list groups: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn create_group(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageUsers>,
+    Json(request): Json<CreateGroupRequest>,
+) -> Result<(StatusCode, Json<ApiResponse<UserGroup>>), Response> {
+    let name = request.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_GROUP_NAME_LEN {
+        return Err(invalid_input(format!("Group name must be 1 to {} characters", MAX_GROUP_NAME_LEN)));
+    }
+
+    match state.db.create_group(name, request.description.as_deref()).await {
+        Ok(Some(group)) => Ok((StatusCode::CREATED, Json(ApiResponse::success(group)))),
+        Ok(None) => Err(invalid_input(format!("A group named '{}' already exists", name))),
+        Err(e) => {
+            tracing::error!("Failed to create group: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+pub async fn delete_group(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageUsers>,
+    Path(group_id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    match state.db.delete_group(&group_id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to delete group: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn list_group_members(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageUsers>,
+    Path(group_id): Path<String>,
+) -> Result<Json<ApiResponse<Vec<GroupMember>>>, StatusCode> {
+    ensure_group_exists(&state, &group_id).await?;
+
+    match state.db.get_group_members(&group_id).await {
+        Ok(members) => Ok(Json(ApiResponse::success(members))),
+        Err(e) => {
+            tracing::error!("Failed to list group members: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn add_group_member(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageUsers>,
+    Path((group_id, user_id)): Path<(String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    ensure_group_exists(&state, &group_id).await?;
+    match state.auth_service.get_user(&user_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+
+    match state.db.add_group_member(&group_id, &user_id).await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(e) => {
+            tracing::error!("Failed to add group member: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn remove_group_member(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageUsers>,
+    Path((group_id, user_id)): Path<(String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    match state.db.remove_group_member(&group_id, &user_id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to remove group member: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// Optionally filtered by user, group or game
+pub async fn list_access_rules(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageUsers>,
+    Query(query): Query<AccessRuleQuery>,
+) -> Result<Json<ApiResponse<Vec<AccessRule>>>, StatusCode> {
+    match state.db.get_access_rules(&query).await {
+        Ok(rules) => Ok(Json(ApiResponse::success(rules))),
+        Err(e) => {
+            tracing::error!("Failed to list access rules: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn create_access_rule(
+    State(state): State<AppState>,
+    Extension(admin): Extension<User>,
+    _: RequirePermission<ManageUsers>,
+    Json(mut request): Json<CreateAccessRuleRequest>,
+) -> Result<(StatusCode, Json<ApiResponse<AccessRule>>), Response> {
+    if request.user_id.is_some() == request.group_id.is_some() {
+        return Err(invalid_input("Set exactly one of user_id and group_id".to_string()));
+    }
+    if request.game_id.is_some() == request.library_root.is_some() {
+        return Err(invalid_input("Set exactly one of game_id and library_root".to_string()));
+    }
+
+    let exists = match (&request.user_id, &request.group_id) {
+        (Some(user_id), _) => state.auth_service.get_user(user_id).await.map(|user| user.is_some()).ok(),
+        (_, Some(group_id)) => state.db.get_group(group_id).await.map(|group| group.is_some()).ok(),
+        (None, None) => Some(false),
+    };
+    match exists {
+        Some(true) => {}
+        Some(false) => return Err(invalid_input("Unknown user or group".to_string())),
+        None => return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+
+    if let Some(game_id) = &request.game_id {
+        match state.db.get_game_by_id(game_id).await {
+            Ok(Some(_)) => {}
+            Ok(None) => return Err(invalid_input("Unknown game".to_string())),
+            Err(e) => {
+                tracing::error!("Failed to get game for access rule: {}", e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+            }
+        }
+    }
+    // Roots are matched as path prefixes, so only the configured ones make sense
+    if let Some(library_root) = &request.library_root {
+        let library_root = library_root.trim_end_matches('/');
+        let root = state
+            .library_scanner
+            .roots()
+            .iter()
+            .map(|root| root.to_string_lossy().trim_end_matches('/').to_string())
+            .find(|root| root == library_root);
+        match root {
+            Some(root) => request.library_root = Some(root),
+            None => return Err(invalid_input("library_root must be one of the configured library roots".to_string())),
+        }
+    }
+
+    match state.db.create_access_rule(&request, &admin.id).await {
+        Ok(rule) => {
+            tracing::info!("Admin {} added access rule {} ({})", admin.username, rule.id, rule.effect);
+            Ok((StatusCode::CREATED, Json(ApiResponse::success(rule))))
+        }
+        Err(e) => {
+            tracing::error!("Failed to create access rule: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+pub async fn delete_access_rule(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageUsers>,
+    Path(rule_id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    match state.db.delete_access_rule(&rule_id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to delete access rule: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn ensure_group_exists(state: &AppState, group_id: &str) -> Result<(), StatusCode> {
+    match state.db.get_group(group_id).await {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get group: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
@@ -12,21 +12,193 @@ pub struct User {
     pub is_admin: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub must_change_password: bool,
+    #[serde(skip_serializing)]
+    pub password_expires_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
 pub struct Session {
     pub id: String,
     pub user_id: String,
     pub token: String,
     pub expires_at: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub device_name: Option<String>,
+    pub last_seen_at: Option<DateTime<Utc>>,
+    pub refresh_expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct RefreshToken {
+    pub id: String,
+    pub session_id: String,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+// Prefix that tells personal access tokens apart from session tokens
+pub const ACCESS_TOKEN_PREFIX: &str = "glpat_";
+
+// Binds a provider sign-in to the browser that started it; holds a hash of the state
+pub const OIDC_STATE_COOKIE: &str = "oidc_state";
+
+// Scopes a personal access token can carry. Sessions from a password login can do everything.
+pub const SCOPES: &[(&str, &str)] = &[
+    ("library:read", "Browse the store and the user's library, manifests and builds"),
+    ("library:write", "Install and uninstall games in the user's library"),
+    ("downloads", "Download game files"),
+    ("admin:games", "Manage games, metadata, builds, jobs and the catalog (needs an admin role)"),
+    ("admin:users", "Manage users, their sessions, roles, groups and game access (needs an admin role)"),
+];
+
+#[derive(Debug, Clone, FromRow)]
+pub struct PersonalAccessToken {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub token_prefix: String,
+    pub scopes: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl PersonalAccessToken {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.split(' ').any(|s| s == scope)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAccessTokenRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    // Never expires when omitted
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccessTokenResponse {
+    pub id: String,
+    pub name: String,
+    pub token_prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<PersonalAccessToken> for AccessTokenResponse {
+    fn from(token: PersonalAccessToken) -> Self {
+        Self {
+            scopes: token.scopes.split(' ').filter(|s| !s.is_empty()).map(str::to_string).collect(),
+            id: token.id,
+            name: token.name,
+            token_prefix: token.token_prefix,
+            expires_at: token.expires_at,
+            last_used_at: token.last_used_at,
+            created_at: token.created_at,
+        }
+    }
+}
+
+// The secret is only ever returned here, when the token is created
+#[derive(Debug, Serialize)]
+pub struct CreatedAccessTokenResponse {
+    pub token: String,
+    #[serde(flatten)]
+    pub info: AccessTokenResponse,
+}
+
+// How long tokens live. The refresh lifetime is an idle timeout: it restarts whenever the
+// session is used. max_session optionally caps a session's total age regardless of activity.
+#[derive(Debug, Clone, Copy)]
+pub struct SessionLifetimes {
+    pub access: chrono::Duration,
+    pub refresh: chrono::Duration,
+    pub max_session: Option<chrono::Duration>,
+}
+
+impl Default for SessionLifetimes {
+    fn default() -> Self {
+        Self {
+            access: chrono::Duration::minutes(15),
+            refresh: chrono::Duration::days(30),
+            max_session: None,
+        }
+    }
+}
+
+// Where a login came from, recorded on the session it creates
+#[derive(Debug, Default)]
+pub struct SessionDevice {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub device_name: Option<String>,
+}
+
+// Failed logins before an account or address is locked out. The first few cost nothing;
+// after that each failure doubles the wait before the next attempt.
+#[derive(Debug, Clone, Copy)]
+pub struct LoginThrottle {
+    pub max_user_failures: i64,
+    pub max_ip_failures: i64,
+    pub lockout: chrono::Duration,
+}
+
+impl Default for LoginThrottle {
+    fn default() -> Self {
+        Self {
+            max_user_failures: 5,
+            max_ip_failures: 50,
+            lockout: chrono::Duration::minutes(15),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
+    // Optional label chosen by the client, e.g. "Living room PC"
+    #[serde(default)]
+    pub device_name: Option<String>,
+}
+
+// A session as shown to its owner or an admin; the token itself is never listed
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    pub id: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub device_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+    pub current: bool,
+}
+
+impl SessionResponse {
+    pub fn new(session: Session, current_session_id: Option<&str>) -> Self {
+        Self {
+            current: current_session_id == Some(session.id.as_str()),
+            id: session.id,
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            device_name: session.device_name,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+            expires_at: session.refresh_expires_at.unwrap_or(session.expires_at),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RevokedSessionsResponse {
+    pub revoked: u64,
+    pub revoked_tokens: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -37,10 +209,44 @@ pub struct CreateUserRequest {
     pub is_admin: bool,
 }
 
+// First-run setup: creates the initial admin while there are no users
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetupRequest {
+    pub username: String,
+    pub password: String,
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SetupStatusResponse {
+    pub setup_required: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+// Returned by login and refresh. `token` is the access token for the Authorization header.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginResponse {
     pub user: UserResponse,
     pub token: String,
+    pub expires_at: DateTime<Utc>,
+    pub refresh_token: String,
+    pub refresh_expires_at: DateTime<Utc>,
+}
+
+impl LoginResponse {
+    pub fn new(user: User, session: Session, refresh_token: String) -> Self {
+        Self {
+            user: user.into(),
+            token: session.token,
+            expires_at: session.expires_at,
+            refresh_token,
+            refresh_expires_at: session.refresh_expires_at.unwrap_or(session.expires_at),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -50,6 +256,8 @@ pub struct UserResponse {
     pub email: Option<String>,
     pub is_admin: bool,
     pub created_at: DateTime<Utc>,
+    // Set after an admin reset; only the password change endpoint works until it is cleared
+    pub must_change_password: bool,
 }
 
 impl From<User> for UserResponse {
@@ -60,16 +268,154 @@ impl From<User> for UserResponse {
             email: user.email,
             is_admin: user.is_admin,
             created_at: user.created_at,
+            must_change_password: user.must_change_password,
         }
     }
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChangePasswordResponse {
+    // Other sessions of the user that were signed out
+    pub revoked_sessions: u64,
+    // Personal access tokens, which all end with the old password
+    pub revoked_tokens: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PasswordResetResponse {
+    pub temporary_password: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct Role {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub built_in: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+// Creates a custom role, or replaces one's description and permissions
+#[derive(Debug, Deserialize)]
+pub struct RoleRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoleResponse {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub built_in: bool,
+    // Empty for superuser, which implicitly has every permission
+    pub permissions: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl RoleResponse {
+    pub fn new(role: Role, permissions: Vec<String>) -> Self {
+        Self {
+            id: role.id,
+            name: role.name,
+            description: role.description,
+            built_in: role.built_in,
+            permissions,
+            created_at: role.created_at,
+        }
+    }
+}
+
+// An account at the OpenID Connect provider that signs in as a local user
+#[derive(Debug, Serialize, FromRow)]
+pub struct UserIdentity {
+    pub id: String,
+    #[serde(skip_serializing)]
+    pub user_id: String,
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_login_at: Option<DateTime<Utc>>,
+}
+
+// A sign-in between the redirect to the provider and its callback
+#[derive(Debug, FromRow)]
+pub struct OidcLoginState {
+    pub nonce: String,
+    pub code_verifier: String,
+    pub link_user_id: Option<String>,
+    pub redirect_to: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+// A provider account waiting for its user to confirm linking it
+#[derive(Debug, FromRow)]
+pub struct OidcPendingLink {
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+// What a completed provider sign-in did
+pub enum OidcOutcome {
+    LoggedIn { session: Box<Session>, refresh_token: String },
+    // The user must confirm the link from their session before it takes effect
+    LinkPending { link_id: String },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OidcLoginQuery {
+    // Local path, or an allowed absolute URL, to return to with the tokens in the fragment
+    pub redirect_to: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OidcStatusResponse {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuthorizationUrlResponse {
+    pub authorization_url: String,
+}
+
 #[derive(Debug)]
 pub enum AuthError {
     InvalidCredentials,
     UserNotFound,
     UsernameExists,
     SessionExpired,
+    RefreshTokenReused,
+    // Seconds until the next attempt is allowed
+    TooManyAttempts(i64),
+    SetupCompleted,
+    // No accounts exist yet; the first one must come from the setup endpoint
+    SetupRequired,
+    InvalidInput(String),
+    // Signed in at the provider, but no local account and automatic creation is off
+    IdentityNotLinked,
+    // The provider account already belongs to another user
+    IdentityInUse,
+    // Discovery, the token exchange or ID token validation failed; details are logged
+    ProviderError,
     #[allow(dead_code)]
     Unauthorized,  // Added allow(dead_code) to suppress the warning
     InternalError,
@@ -82,7 +428,15 @@ impl std::fmt::Display for AuthError {
             AuthError::UserNotFound => write!(f, "User not found"),
             AuthError::UsernameExists => write!(f, "Username already exists"),
             AuthError::SessionExpired => write!(f, "Session expired"),
+            AuthError::RefreshTokenReused => write!(f, "Refresh token already used"),
+            AuthError::TooManyAttempts(seconds) => write!(f, "Too many failed attempts; retry in {} seconds", seconds),
             AuthError::Unauthorized => write!(f, "Unauthorized access"),
+            AuthError::SetupCompleted => write!(f, "Setup has already been completed"),
+            AuthError::SetupRequired => write!(f, "Complete the initial setup before signing in with single sign-on"),
+            AuthError::InvalidInput(message) => write!(f, "{}", message),
+            AuthError::IdentityNotLinked => write!(f, "No account is linked to this sign-in; ask an admin to create one"),
+            AuthError::IdentityInUse => write!(f, "This sign-in is already linked to another account"),
+            AuthError::ProviderError => write!(f, "Sign-in with the identity provider failed"),
             AuthError::InternalError => write!(f, "Internal server error"),
         }
     }
@@ -1,39 +1,364 @@
 use axum::{
-    extract::{Extension, State, Path},
-    http::StatusCode,
-    response::Json,
+    extract::{ConnectInfo, Extension, State, Path, Query},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Json, Response},
 };
 use axum_macros::debug_handler;
+use std::net::SocketAddr;
 use crate::{
-    auth::{LoginRequest, CreateUserRequest, LoginResponse, UserResponse, User},
-    handlers::{AppState, ApiResponse},
+    auth::{
+        LoginRequest, CreateUserRequest, LoginResponse, UserResponse, User, Session, SessionDevice,
+        SessionResponse, RevokedSessionsResponse, RefreshRequest, AuthError, SetupRequest,
+        SetupStatusResponse, ChangePasswordRequest, ChangePasswordResponse, PasswordResetResponse,
+        CreateAccessTokenRequest, AccessTokenResponse, CreatedAccessTokenResponse, RoleRequest,
+        RoleResponse, UserIdentity, OIDC_STATE_COOKIE, OidcOutcome, OidcLoginQuery, OidcCallbackQuery, OidcStatusResponse,
+        AuthorizationUrlResponse,
+    },
+    handlers::{client_ip, AppState, ApiResponse},
+    permissions::{self, ManageRoles, ManageUsers, PermissionInfo, Permissions, RequirePermission},
 };
 
+// Validation and password policy failures carry a message the client can show
+pub fn invalid_input(message: String) -> Response {
+    (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(message))).into_response()
+}
+
+#[debug_handler]
+pub async fn setup_status(
+    State(state): State<AppState>,
+) -> Result<Json<ApiResponse<SetupStatusResponse>>, StatusCode> {
+    match state.auth_service.setup_required().await {
+        Ok(setup_required) => Ok(Json(ApiResponse::success(SetupStatusResponse { setup_required }))),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+// Unauthenticated, but only usable on a fresh install with no users
+#[debug_handler]
+pub async fn setup(
+    State(state): State<AppState>,
+    Json(request): Json<SetupRequest>,
+) -> Result<(StatusCode, Json<ApiResponse<UserResponse>>), Response> {
+    match state.auth_service.create_initial_admin(request).await {
+        Ok(user) => Ok((StatusCode::CREATED, Json(ApiResponse::success(user.into())))),
+        Err(AuthError::SetupCompleted) => Err(StatusCode::CONFLICT.into_response()),
+        Err(AuthError::InvalidInput(message)) => Err(invalid_input(message)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+fn session_device(state: &AppState, addr: SocketAddr, headers: &HeaderMap) -> SessionDevice {
+    SessionDevice {
+        user_agent: headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+        ip_address: Some(client_ip(state, addr.ip(), headers).to_string()),
+        device_name: None,
+    }
+}
+
 #[debug_handler]
 #[allow(unused_variables)]
 pub async fn login(
     State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(request): Json<LoginRequest>,
+) -> Result<Json<ApiResponse<LoginResponse>>, Response> {
+    let device = session_device(&state, addr, &headers);
+
+    match state.auth_service.login(request, device).await {
+        Ok((user, session, refresh_token)) => {
+            Ok(Json(ApiResponse::success(LoginResponse::new(user, session, refresh_token))))
+        }
+        Err(AuthError::TooManyAttempts(retry_after)) => {
+            Err((StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, retry_after.to_string())]).into_response())
+        }
+        Err(AuthError::InternalError) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+        Err(_) => Err(StatusCode::UNAUTHORIZED.into_response()),
+    }
+}
+
+#[debug_handler]
+pub async fn oidc_status(
+    State(state): State<AppState>,
+) -> Json<ApiResponse<OidcStatusResponse>> {
+    Json(ApiResponse::success(OidcStatusResponse { enabled: state.auth_service.oidc_enabled() }))
+}
+
+fn oidc_error(error: AuthError) -> Response {
+    let status = match error {
+        AuthError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+        AuthError::IdentityNotLinked | AuthError::SetupRequired => StatusCode::FORBIDDEN,
+        AuthError::IdentityInUse => StatusCode::CONFLICT,
+        AuthError::ProviderError => StatusCode::BAD_GATEWAY,
+        _ => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    (status, Json(ApiResponse::<()>::error(error.to_string()))).into_response()
+}
+
+// 303 to a URL that may carry tokens in its fragment; never panics on a bad header value
+fn see_other(location: &str) -> Response {
+    match HeaderValue::try_from(location) {
+        Ok(location) => (StatusCode::SEE_OTHER, [(header::LOCATION, location)]).into_response(),
+        Err(_) => {
+            tracing::error!("Refusing to redirect to an invalid URL");
+            oidc_error(AuthError::InvalidInput("Invalid redirect URL".to_string()))
+        }
+    }
+}
+
+// Browsers are sent here to sign in with the identity provider
+#[debug_handler]
+pub async fn oidc_login(
+    State(state): State<AppState>,
+    Query(query): Query<OidcLoginQuery>,
+) -> Result<Response, Response> {
+    if !state.auth_service.oidc_enabled() {
+        return Err(StatusCode::NOT_FOUND.into_response());
+    }
+
+    match state.auth_service.begin_oidc_login(None, query.redirect_to.as_deref()).await {
+        Ok((authorization_url, state_cookie)) => Ok((
+            [(header::SET_COOKIE, state.auth_service.oidc_state_cookie(&state_cookie))],
+            see_other(&authorization_url),
+        ).into_response()),
+        Err(e) => Err(oidc_error(e)),
+    }
+}
+
+fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+// The provider redirects back here. The tokens go to the client in the URL fragment of
+// redirect_to, which browsers do not send to servers or put in Referer headers.
+#[debug_handler]
+pub async fn oidc_callback(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(query): Query<OidcCallbackQuery>,
+) -> Result<Response, Response> {
+    if let Some(error) = query.error {
+        tracing::warn!("OIDC provider returned {}: {}", error, query.error_description.unwrap_or_default());
+        return Err(oidc_error(AuthError::ProviderError));
+    }
+    let (Some(login_state), Some(code)) = (query.state, query.code) else {
+        return Err(oidc_error(AuthError::InvalidInput("Missing code or state".to_string())));
+    };
+
+    let device = session_device(&state, addr, &headers);
+
+    let (user, outcome, redirect_to) = state
+        .auth_service
+        .complete_oidc_login(&login_state, cookie(&headers, OIDC_STATE_COOKIE), &code, device)
+        .await
+        .map_err(oidc_error)?;
+
+    let mut fragment = url::form_urlencoded::Serializer::new(String::new());
+    match outcome {
+        OidcOutcome::LoggedIn { session, refresh_token } => {
+            let response = LoginResponse::new(user, *session, refresh_token);
+            fragment
+                .append_pair("token", &response.token)
+                .append_pair("expires_at", &response.expires_at.to_rfc3339())
+                .append_pair("refresh_token", &response.refresh_token)
+                .append_pair("refresh_expires_at", &response.refresh_expires_at.to_rfc3339());
+        }
+        OidcOutcome::LinkPending { link_id } => {
+            fragment.append_pair("link_id", &link_id);
+        }
+    }
+    let target = redirect_to.split('#').next().unwrap_or_default();
+    Ok((
+        [(header::SET_COOKIE, state.auth_service.oidc_state_cookie(""))],
+        see_other(&format!("{}#{}", target, fragment.finish())),
+    ).into_response())
+}
+
+// Starts linking a provider account to the signed-in user; the client opens the returned URL
+// in the same browser, then confirms the link_id it gets back
+#[debug_handler]
+pub async fn oidc_link(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Json(query): Json<OidcLoginQuery>,
+) -> Result<Response, Response> {
+    if !state.auth_service.oidc_enabled() {
+        return Err(StatusCode::NOT_FOUND.into_response());
+    }
+
+    match state.auth_service.begin_oidc_login(Some(&user.id), query.redirect_to.as_deref()).await {
+        Ok((authorization_url, state_cookie)) => Ok((
+            [(header::SET_COOKIE, state.auth_service.oidc_state_cookie(&state_cookie))],
+            Json(ApiResponse::success(AuthorizationUrlResponse { authorization_url })),
+        ).into_response()),
+        Err(e) => Err(oidc_error(e)),
+    }
+}
+
+#[debug_handler]
+pub async fn confirm_oidc_link(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(link_id): Path<String>,
+) -> Result<Json<ApiResponse<UserIdentity>>, Response> {
+    match state.auth_service.confirm_oidc_link(&user, &link_id).await {
+        Ok(Some(identity)) => Ok(Json(ApiResponse::success(identity))),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => Err(oidc_error(e)),
+    }
+}
+
+#[debug_handler]
+pub async fn list_identities(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> Result<Json<ApiResponse<Vec<UserIdentity>>>, StatusCode> {
+    match state.auth_service.get_user_identities(&user.id).await {
+        Ok(identities) => Ok(Json(ApiResponse::success(identities))),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[debug_handler]
+pub async fn unlink_identity(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(identity_id): Path<String>,
+) -> Result<StatusCode, Response> {
+    match state.auth_service.unlink_identity(&user, &identity_id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(AuthError::InvalidInput(message)) => Err(invalid_input(message)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+#[debug_handler]
+pub async fn refresh(
+    State(state): State<AppState>,
+    Json(request): Json<RefreshRequest>,
 ) -> Result<Json<ApiResponse<LoginResponse>>, StatusCode> {
-    match state.auth_service.login(request).await {
-        Ok((user, session)) => {
-            let response = LoginResponse {
-                user: user.into(),
-                token: session.token,
-            };
-            Ok(Json(ApiResponse::success(response)))
+    match state.auth_service.refresh(&request.refresh_token).await {
+        Ok((user, session, refresh_token)) => {
+            Ok(Json(ApiResponse::success(LoginResponse::new(user, session, refresh_token))))
         }
+        Err(AuthError::InternalError) => Err(StatusCode::INTERNAL_SERVER_ERROR),
         Err(_) => Err(StatusCode::UNAUTHORIZED),
     }
 }
 
 #[debug_handler]
-#[allow(unused_variables)]
 pub async fn logout(
-    State(_state): State<AppState>,
-    Extension(_user): Extension<User>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+) -> Result<StatusCode, StatusCode> {
+    match state.auth_service.logout(&session.token).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[debug_handler]
+pub async fn change_password(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(session): Extension<Session>,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Result<Json<ApiResponse<ChangePasswordResponse>>, Response> {
+    match state.auth_service.change_password(&user, &session.id, request).await {
+        Ok((revoked_sessions, revoked_tokens)) => {
+            Ok(Json(ApiResponse::success(ChangePasswordResponse { revoked_sessions, revoked_tokens })))
+        }
+        // Not 401: the session is fine, the current password was wrong
+        Err(AuthError::InvalidCredentials) => Err(StatusCode::FORBIDDEN.into_response()),
+        Err(AuthError::TooManyAttempts(retry_after)) => {
+            Err((StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, retry_after.to_string())]).into_response())
+        }
+        Err(AuthError::InvalidInput(message)) => Err(invalid_input(message)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+#[debug_handler]
+pub async fn list_access_tokens(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> Result<Json<ApiResponse<Vec<AccessTokenResponse>>>, StatusCode> {
+    match state.auth_service.get_access_tokens(&user.id).await {
+        Ok(tokens) => Ok(Json(ApiResponse::success(tokens.into_iter().map(|t| t.into()).collect()))),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[debug_handler]
+pub async fn create_access_token(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Json(request): Json<CreateAccessTokenRequest>,
+) -> Result<(StatusCode, Json<ApiResponse<CreatedAccessTokenResponse>>), Response> {
+    match state.auth_service.create_access_token(&user, request).await {
+        Ok((access_token, token)) => {
+            let response = CreatedAccessTokenResponse { token, info: access_token.into() };
+            Ok((StatusCode::CREATED, Json(ApiResponse::success(response))))
+        }
+        Err(AuthError::InvalidInput(message)) => Err(invalid_input(message)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+#[debug_handler]
+pub async fn revoke_access_token(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(token_id): Path<String>,
 ) -> Result<StatusCode, StatusCode> {
-    Ok(StatusCode::OK)
+    match state.auth_service.revoke_access_token(&user.id, &token_id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[debug_handler]
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(session): Extension<Session>,
+) -> Result<Json<ApiResponse<Vec<SessionResponse>>>, StatusCode> {
+    match state.auth_service.get_user_sessions(&user.id).await {
+        Ok(sessions) => {
+            let sessions = sessions
+                .into_iter()
+                .map(|s| SessionResponse::new(s, Some(&session.id)))
+                .collect();
+            Ok(Json(ApiResponse::success(sessions)))
+        }
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[debug_handler]
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(session_id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    match state.auth_service.revoke_session(&user.id, &session_id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
 }
 
 #[debug_handler]
@@ -43,16 +368,34 @@ pub async fn me(
     Json(ApiResponse::success(user.into()))
 }
 
+// Refuses to act on a user who can do things the caller cannot, e.g. a user manager
+// resetting a superuser's password to take over the account
+async fn ensure_covers(state: &AppState, permissions: &Permissions, target: &User) -> Result<(), StatusCode> {
+    match state.auth_service.get_permissions(target).await {
+        Ok(target_permissions) if permissions.covers(&target_permissions) => Ok(()),
+        Ok(_) => Err(StatusCode::FORBIDDEN),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
 #[debug_handler]
 #[allow(unused_variables)]
 pub async fn create_user(
     State(state): State<AppState>,
+    _: RequirePermission<ManageUsers>,
     Extension(_admin): Extension<User>,
+    Extension(permissions): Extension<Permissions>,
     Json(request): Json<CreateUserRequest>,
-) -> Result<Json<ApiResponse<UserResponse>>, StatusCode> {
+) -> Result<Json<ApiResponse<UserResponse>>, Response> {
+    // Admins are superusers, which only a superuser may create
+    if request.is_admin && !permissions.covers(&Permissions::all()) {
+        return Err(StatusCode::FORBIDDEN.into_response());
+    }
+
     match state.auth_service.create_user(request).await {
         Ok(user) => Ok(Json(ApiResponse::success(user.into()))),
-        Err(_) => Err(StatusCode::BAD_REQUEST),
+        Err(AuthError::InvalidInput(message)) => Err(invalid_input(message)),
+        Err(_) => Err(StatusCode::BAD_REQUEST.into_response()),
     }
 }
 
@@ -60,6 +403,7 @@ pub async fn create_user(
 #[allow(unused_variables)]
 pub async fn list_users(
     State(state): State<AppState>,
+    _: RequirePermission<ManageUsers>,
     Extension(_admin): Extension<User>,
 ) -> Result<Json<ApiResponse<Vec<UserResponse>>>, StatusCode> {
     match state.auth_service.get_all_users().await {
@@ -75,11 +419,274 @@ pub async fn list_users(
 #[allow(unused_variables)]
 pub async fn delete_user(
     State(state): State<AppState>,
+    _: RequirePermission<ManageUsers>,
     Extension(_admin): Extension<User>,
+    Extension(permissions): Extension<Permissions>,
     Path(user_id): Path<String>,
-) -> Result<StatusCode, StatusCode> {
+) -> Result<StatusCode, Response> {
+    let user = match state.auth_service.get_user(&user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(StatusCode::NOT_FOUND.into_response()),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    };
+    ensure_covers(&state, &permissions, &user).await.map_err(IntoResponse::into_response)?;
+
     match state.auth_service.delete_user(&user_id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(AuthError::InvalidInput(message)) => Err(invalid_input(message)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+#[debug_handler]
+pub async fn list_user_sessions(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageUsers>,
+    Extension(permissions): Extension<Permissions>,
+    Path(user_id): Path<String>,
+) -> Result<Json<ApiResponse<Vec<SessionResponse>>>, StatusCode> {
+    let user = match state.auth_service.get_user(&user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    ensure_covers(&state, &permissions, &user).await?;
+
+    match state.auth_service.get_user_sessions(&user_id).await {
+        Ok(sessions) => {
+            let sessions = sessions.into_iter().map(|s| SessionResponse::new(s, None)).collect();
+            Ok(Json(ApiResponse::success(sessions)))
+        }
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+// Force-logout: every session the user has, on every device, and their personal access tokens
+#[debug_handler]
+pub async fn revoke_user_sessions(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageUsers>,
+    Extension(admin): Extension<User>,
+    Extension(permissions): Extension<Permissions>,
+    Path(user_id): Path<String>,
+) -> Result<Json<ApiResponse<RevokedSessionsResponse>>, StatusCode> {
+    let user = match state.auth_service.get_user(&user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    ensure_covers(&state, &permissions, &user).await?;
+
+    match state.auth_service.revoke_user_sessions(&user_id).await {
+        Ok((revoked, revoked_tokens)) => {
+            tracing::info!(
+                "Admin {} revoked {} session(s) and {} access token(s) of user {}",
+                admin.username, revoked, revoked_tokens, user_id
+            );
+            Ok(Json(ApiResponse::success(RevokedSessionsResponse { revoked, revoked_tokens })))
+        }
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[debug_handler]
+pub async fn unlock_user(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageUsers>,
+    Extension(permissions): Extension<Permissions>,
+    Path(user_id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let user = match state.auth_service.get_user(&user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    ensure_covers(&state, &permissions, &user).await?;
+
+    match state.auth_service.unlock_user(&user).await {
         Ok(_) => Ok(StatusCode::NO_CONTENT),
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     }
 }
+
+#[debug_handler]
+pub async fn reset_user_password(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageUsers>,
+    Extension(admin): Extension<User>,
+    Extension(permissions): Extension<Permissions>,
+    Path(user_id): Path<String>,
+) -> Result<Json<ApiResponse<PasswordResetResponse>>, StatusCode> {
+    let user = match state.auth_service.get_user(&user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    ensure_covers(&state, &permissions, &user).await?;
+
+    match state.auth_service.reset_password(&user).await {
+        Ok((temporary_password, expires_at)) => {
+            tracing::info!("Admin {} reset the password of {}", admin.username, user.username);
+            Ok(Json(ApiResponse::success(PasswordResetResponse { temporary_password, expires_at })))
+        }
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[debug_handler]
+pub async fn list_permissions(
+    _: RequirePermission<ManageRoles>,
+) -> Json<ApiResponse<Vec<PermissionInfo>>> {
+    Json(ApiResponse::success(permissions::all_permissions()))
+}
+
+#[debug_handler]
+pub async fn list_roles(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageRoles>,
+) -> Result<Json<ApiResponse<Vec<RoleResponse>>>, StatusCode> {
+    match state.auth_service.get_roles().await {
+        Ok(roles) => {
+            let roles = roles.into_iter().map(|(role, granted)| RoleResponse::new(role, granted)).collect();
+            Ok(Json(ApiResponse::success(roles)))
+        }
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[debug_handler]
+pub async fn create_role(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageRoles>,
+    Extension(permissions): Extension<Permissions>,
+    Json(request): Json<RoleRequest>,
+) -> Result<(StatusCode, Json<ApiResponse<RoleResponse>>), Response> {
+    // Otherwise a role manager could make a role with more than they have and assign it
+    if !permissions.covers(&Permissions::from_granted(request.permissions.iter().cloned())) {
+        return Err(StatusCode::FORBIDDEN.into_response());
+    }
+
+    match state.auth_service.create_role(request).await {
+        Ok((role, granted)) => Ok((StatusCode::CREATED, Json(ApiResponse::success(RoleResponse::new(role, granted))))),
+        Err(AuthError::InvalidInput(message)) => Err(invalid_input(message)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+#[debug_handler]
+pub async fn update_role(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageRoles>,
+    Extension(permissions): Extension<Permissions>,
+    Path(role_id): Path<String>,
+    Json(request): Json<RoleRequest>,
+) -> Result<Json<ApiResponse<RoleResponse>>, Response> {
+    // Both what the role grants now and what it would grant must be within the caller's reach
+    let current = match state.auth_service.get_role(&role_id).await {
+        Ok(Some((_, granted))) => permissions::role_grants(&role_id, &granted),
+        Ok(None) => return Err(StatusCode::NOT_FOUND.into_response()),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    };
+    if !permissions.covers(&current) || !permissions.covers(&Permissions::from_granted(request.permissions.iter().cloned())) {
+        return Err(StatusCode::FORBIDDEN.into_response());
+    }
+
+    match state.auth_service.update_role(&role_id, request).await {
+        Ok(Some((role, granted))) => Ok(Json(ApiResponse::success(RoleResponse::new(role, granted)))),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(AuthError::InvalidInput(message)) => Err(invalid_input(message)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+#[debug_handler]
+pub async fn delete_role(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageRoles>,
+    Path(role_id): Path<String>,
+) -> Result<StatusCode, Response> {
+    match state.auth_service.delete_role(&role_id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(AuthError::InvalidInput(message)) => Err(invalid_input(message)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+#[debug_handler]
+pub async fn list_user_roles(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageRoles>,
+    Path(user_id): Path<String>,
+) -> Result<Json<ApiResponse<Vec<RoleResponse>>>, StatusCode> {
+    match state.auth_service.get_user(&user_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+
+    match state.auth_service.get_user_roles(&user_id).await {
+        Ok(roles) => {
+            let roles = roles.into_iter().map(|(role, granted)| RoleResponse::new(role, granted)).collect();
+            Ok(Json(ApiResponse::success(roles)))
+        }
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+// Only roles granting nothing beyond the caller's own permissions may be handed out or taken
+// away; in particular only a superuser can grant superuser. 404 when the role does not exist.
+async fn ensure_role_covered(state: &AppState, permissions: &Permissions, role_id: &str) -> Result<(), StatusCode> {
+    match state.auth_service.get_role(role_id).await {
+        Ok(Some((_, granted))) if permissions.covers(&permissions::role_grants(role_id, &granted)) => Ok(()),
+        Ok(Some(_)) => Err(StatusCode::FORBIDDEN),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[debug_handler]
+pub async fn assign_user_role(
+    State(state): State<AppState>,
+    Extension(admin): Extension<User>,
+    Extension(permissions): Extension<Permissions>,
+    _: RequirePermission<ManageRoles>,
+    Path((user_id, role_id)): Path<(String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    let user = match state.auth_service.get_user(&user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    ensure_role_covered(&state, &permissions, &role_id).await?;
+
+    match state.auth_service.assign_role(&user_id, &role_id).await {
+        Ok(()) => {
+            tracing::info!("Admin {} gave {} the role {}", admin.username, user.username, role_id);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[debug_handler]
+pub async fn unassign_user_role(
+    State(state): State<AppState>,
+    Extension(admin): Extension<User>,
+    Extension(permissions): Extension<Permissions>,
+    _: RequirePermission<ManageRoles>,
+    Path((user_id, role_id)): Path<(String, String)>,
+) -> Result<StatusCode, Response> {
+    ensure_role_covered(&state, &permissions, &role_id).await.map_err(IntoResponse::into_response)?;
+
+    match state.auth_service.unassign_role(&user_id, &role_id).await {
+        Ok(true) => {
+            tracing::info!("Admin {} removed the role {} from user {}", admin.username, role_id, user_id);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(false) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(AuthError::InvalidInput(message)) => Err(invalid_input(message)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
@@ -0,0 +1,351 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+use crate::{database::Database, models::Game};
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CatalogFormat {
+    Json,
+    Csv,
+}
+
+impl CatalogFormat {
+    pub fn from_path(path: &str) -> Self {
+        if path.to_lowercase().ends_with(".csv") {
+            CatalogFormat::Csv
+        } else {
+            CatalogFormat::Json
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            CatalogFormat::Json => "application/json",
+            CatalogFormat::Csv => "text/csv; charset=utf-8",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CatalogFormat::Json => "json",
+            CatalogFormat::Csv => "csv",
+        }
+    }
+}
+
+// One portable row of the catalog. JSON-valued columns (screenshots, genres, platforms,
+// metadata_overrides) stay as JSON text so the CSV form is a flat table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    pub id: Option<String>,
+    pub igdb_id: Option<i64>,
+    pub name: String,
+    pub summary: Option<String>,
+    pub storyline: Option<String>,
+    pub rating: Option<f64>,
+    pub release_date: Option<DateTime<Utc>>,
+    pub cover_url: Option<String>,
+    pub screenshots: Option<String>,
+    pub genres: Option<String>,
+    pub platforms: Option<String>,
+    pub developer: Option<String>,
+    pub publisher: Option<String>,
+    pub file_path: Option<String>,
+    pub file_size: Option<i64>,
+    pub is_available: Option<bool>,
+    pub metadata_overrides: Option<String>,
+    #[serde(default)]
+    pub metadata_provider: Option<String>,
+    #[serde(default)]
+    pub metadata_id: Option<String>,
+}
+
+impl From<Game> for CatalogEntry {
+    fn from(game: Game) -> Self {
+        Self {
+            id: Some(game.id),
+            igdb_id: game.igdb_id,
+            name: game.name,
+            summary: game.summary,
+            storyline: game.storyline,
+            rating: game.rating,
+            release_date: game.release_date,
+            cover_url: game.cover_url,
+            screenshots: game.screenshots,
+            genres: game.genres,
+            platforms: game.platforms,
+            developer: game.developer,
+            publisher: game.publisher,
+            file_path: game.file_path,
+            file_size: game.file_size,
+            is_available: Some(game.is_available),
+            metadata_overrides: game.metadata_overrides,
+            metadata_provider: game.metadata_provider,
+            metadata_id: game.metadata_id,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CatalogExport {
+    pub format_version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub games: Vec<CatalogEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+    Create,
+    Update,
+    Unchanged,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: serde_json::Value,
+    pub new: serde_json::Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportChange {
+    pub action: ImportAction,
+    pub id: String,
+    pub name: String,
+    pub matched_by: Option<&'static str>,
+    pub fields: Vec<FieldChange>,
+}
+
+// A row the import refuses. Rows count from 1 in file order, not counting a CSV header.
+#[derive(Debug, Serialize)]
+pub struct RowError {
+    pub row: usize,
+    pub id: Option<String>,
+    pub name: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub changes: Vec<ImportChange>,
+    // Nothing is written unless this is empty
+    pub errors: Vec<RowError>,
+}
+
+pub async fn export(db: &Database, format: CatalogFormat) -> Result<Vec<u8>> {
+    let games: Vec<CatalogEntry> = db.get_all_games().await?.into_iter().map(CatalogEntry::from).collect();
+
+    match format {
+        CatalogFormat::Json => {
+            let export = CatalogExport {
+                format_version: 1,
+                exported_at: Utc::now(),
+                games,
+            };
+            Ok(serde_json::to_vec_pretty(&export)?)
+        }
+        CatalogFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            for game in &games {
+                writer.serialize(game)?;
+            }
+            Ok(writer.into_inner().map_err(|e| anyhow!("failed to finish CSV: {}", e))?)
+        }
+    }
+}
+
+pub fn parse(data: &[u8], format: CatalogFormat) -> Result<Vec<CatalogEntry>> {
+    match format {
+        CatalogFormat::Json => {
+            // Accept both the export envelope and a bare array of games
+            let value: serde_json::Value = serde_json::from_slice(data)?;
+            if value.is_array() {
+                Ok(serde_json::from_value(value)?)
+            } else {
+                Ok(serde_json::from_value::<CatalogExport>(value)?.games)
+            }
+        }
+        CatalogFormat::Csv => {
+            let mut reader = csv::Reader::from_reader(data);
+            let entries = reader.deserialize().collect::<std::result::Result<Vec<CatalogEntry>, _>>()?;
+            Ok(entries)
+        }
+    }
+}
+
+// Problems visible from the file alone: missing names and ids or igdb_ids used twice
+fn validate(entries: &[CatalogEntry]) -> Vec<RowError> {
+    let mut errors = Vec::new();
+    let mut ids: HashMap<&str, usize> = HashMap::new();
+    let mut igdb_ids: HashMap<i64, usize> = HashMap::new();
+
+    for (index, entry) in entries.iter().enumerate() {
+        let row = index + 1;
+        let mut fail = |error: String| errors.push(RowError {
+            row,
+            id: entry.id.clone(),
+            name: entry.name.clone(),
+            error,
+        });
+
+        if entry.name.trim().is_empty() {
+            fail("name is empty".to_string());
+        }
+        match entry.id.as_deref() {
+            Some(id) if id.trim().is_empty() => fail("id is empty".to_string()),
+            Some(id) => {
+                if let Some(first) = ids.insert(id, row) {
+                    fail(format!("id {} is also used by row {}", id, first));
+                    ids.insert(id, first);
+                }
+            }
+            None => {}
+        }
+        if let Some(igdb_id) = entry.igdb_id {
+            if let Some(first) = igdb_ids.insert(igdb_id, row) {
+                fail(format!("igdb_id {} is also used by row {}", igdb_id, first));
+                igdb_ids.insert(igdb_id, first);
+            }
+        }
+    }
+
+    errors
+}
+
+// Upsert entries by id, falling back to igdb_id. Every catalog field of a matched game is
+// replaced by the imported row; with dry_run the diff is reported and nothing is written.
+// The whole file is checked first: if any row is invalid the report lists the errors and
+// nothing is written either.
+pub async fn import(db: &Database, entries: Vec<CatalogEntry>, dry_run: bool) -> Result<ImportReport> {
+    let existing = db.get_all_games().await?;
+    let by_id: HashMap<String, CatalogEntry> = existing
+        .into_iter()
+        .map(|game| (game.id.clone(), CatalogEntry::from(game)))
+        .collect();
+    let by_igdb_id: HashMap<i64, String> = by_id
+        .values()
+        .filter_map(|entry| Some((entry.igdb_id?, entry.id.clone()?)))
+        .collect();
+
+    let mut report = ImportReport {
+        dry_run,
+        created: 0,
+        updated: 0,
+        unchanged: 0,
+        changes: Vec::new(),
+        errors: validate(&entries),
+    };
+    let mut creates = Vec::new();
+    let mut updates = Vec::new();
+    // Game id -> first row that resolved to it, so two rows cannot both update one game
+    let mut targets: HashMap<String, usize> = HashMap::new();
+
+    for (index, mut entry) in entries.into_iter().enumerate() {
+        let row = index + 1;
+        let matched = match entry.id.as_deref() {
+            Some(id) if by_id.contains_key(id) => Some((id.to_string(), "id")),
+            _ => entry
+                .igdb_id
+                .and_then(|igdb_id| by_igdb_id.get(&igdb_id))
+                .map(|id| (id.clone(), "igdb_id")),
+        };
+
+        match matched {
+            Some((id, matched_by)) => {
+                if let Some(first) = targets.insert(id.clone(), row) {
+                    targets.insert(id.clone(), first);
+                    // Rows that already clash on id or igdb_id are reported once
+                    if !report.errors.iter().any(|error| error.row == row) {
+                        report.errors.push(RowError {
+                            row,
+                            id: entry.id.clone(),
+                            name: entry.name.clone(),
+                            error: format!("matches game {} by {}, which row {} also updates", id, matched_by, first),
+                        });
+                    }
+                }
+                entry.id = Some(id.clone());
+                if entry.is_available.is_none() {
+                    entry.is_available = by_id[&id].is_available;
+                }
+
+                let fields = diff(&by_id[&id], &entry)?;
+                let action = if fields.is_empty() {
+                    report.unchanged += 1;
+                    ImportAction::Unchanged
+                } else {
+                    report.updated += 1;
+                    updates.push(entry.clone());
+                    ImportAction::Update
+                };
+
+                report.changes.push(ImportChange {
+                    action,
+                    id,
+                    name: entry.name,
+                    matched_by: Some(matched_by),
+                    fields,
+                });
+            }
+            None => {
+                let id = entry.id.clone().unwrap_or_else(|| Uuid::new_v4().to_string());
+                entry.id = Some(id.clone());
+                report.created += 1;
+                creates.push(entry.clone());
+
+                report.changes.push(ImportChange {
+                    action: ImportAction::Create,
+                    id,
+                    name: entry.name,
+                    matched_by: None,
+                    fields: Vec::new(),
+                });
+            }
+        }
+    }
+
+    report.errors.sort_by_key(|error| error.row);
+
+    if !dry_run && report.errors.is_empty() {
+        db.apply_catalog_import(&creates, &updates).await?;
+        tracing::info!(
+            "Catalog import applied: {} created, {} updated, {} unchanged",
+            report.created,
+            report.updated,
+            report.unchanged
+        );
+    }
+
+    Ok(report)
+}
+
+fn diff(old: &CatalogEntry, new: &CatalogEntry) -> Result<Vec<FieldChange>> {
+    let old = serde_json::to_value(old)?;
+    let new = serde_json::to_value(new)?;
+
+    let (old, new) = match (old, new) {
+        (serde_json::Value::Object(old), serde_json::Value::Object(new)) => (old, new),
+        _ => return Ok(Vec::new()),
+    };
+
+    Ok(new
+        .into_iter()
+        .filter(|(field, _)| field != "id")
+        .filter_map(|(field, new_value)| {
+            let old_value = old.get(&field).cloned().unwrap_or(serde_json::Value::Null);
+            (old_value != new_value).then_some(FieldChange {
+                field,
+                old: old_value,
+                new: new_value,
+            })
+        })
+        .collect())
+}
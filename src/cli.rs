@@ -0,0 +1,95 @@
+use anyhow::{Result, anyhow};
+use std::io::{BufRead, Write};
+use crate::{
+    auth::{AuthError, CreateUserRequest, SetupRequest},
+    auth_service::AuthService,
+    catalog::{self, CatalogFormat},
+    database::Database,
+    password_policy::PasswordPolicy,
+};
+
+const USAGE: &str = "\
+Usage: game-library-server [COMMAND]
+
+Without a command the HTTP server is started.
+
+Commands:
+  export-catalog <FILE>               Write the games catalog to FILE (.json or .csv)
+  import-catalog <FILE> [--dry-run]   Upsert games from FILE by id or igdb_id
+  create-admin <USERNAME> [--email EMAIL] [--force]
+                                      Create the first admin account; the password is read
+                                      from stdin. --force adds an admin even if users exist";
+
+pub async fn run(db: &Database, password_policy: PasswordPolicy, args: &[String]) -> Result<()> {
+    match args[0].as_str() {
+        "export-catalog" => {
+            let path = args.get(1).ok_or_else(|| anyhow!("missing output file\n\n{}", USAGE))?;
+            let data = catalog::export(db, CatalogFormat::from_path(path)).await?;
+            std::fs::write(path, data)?;
+            println!("Catalog exported to {}", path);
+        }
+        "import-catalog" => {
+            let path = args.get(1).ok_or_else(|| anyhow!("missing input file\n\n{}", USAGE))?;
+            let dry_run = args.iter().any(|arg| arg == "--dry-run");
+            let data = std::fs::read(path)?;
+            let entries = catalog::parse(&data, CatalogFormat::from_path(path))?;
+            let report = catalog::import(db, entries, dry_run).await?;
+
+            for change in &report.changes {
+                let fields: Vec<&str> = change.fields.iter().map(|f| f.field.as_str()).collect();
+                println!("{:?}\t{}\t{}\t{}", change.action, change.id, change.name, fields.join(","));
+            }
+            for error in &report.errors {
+                eprintln!("row {}\t{}\t{}", error.row, error.name, error.error);
+            }
+            if !report.errors.is_empty() {
+                return Err(anyhow!("catalog has invalid rows; nothing was imported"));
+            }
+            println!(
+                "{}{} created, {} updated, {} unchanged",
+                if dry_run { "[dry run] " } else { "" },
+                report.created,
+                report.updated,
+                report.unchanged
+            );
+        }
+        "create-admin" => {
+            let username = args.get(1).ok_or_else(|| anyhow!("missing username\n\n{}", USAGE))?;
+            let email = args.iter().position(|arg| arg == "--email").and_then(|i| args.get(i + 1)).cloned();
+            let force = args.iter().any(|arg| arg == "--force");
+            let password = read_password()?;
+
+            let auth_service = AuthService::new(db.get_pool().clone()).with_password_policy(password_policy);
+            let user = if force {
+                let request = CreateUserRequest { username: username.clone(), password, email, is_admin: true };
+                auth_service.create_user(request).await?
+            } else {
+                let request = SetupRequest { username: username.clone(), password, email };
+                auth_service.create_initial_admin(request).await.map_err(|e| match e {
+                    AuthError::SetupCompleted => {
+                        anyhow!("users already exist; pass --force to add another admin")
+                    }
+                    other => other.into(),
+                })?
+            };
+            println!("Admin {} created ({})", user.username, user.id);
+        }
+        "help" | "--help" | "-h" => println!("{}", USAGE),
+        other => return Err(anyhow!("unknown command '{}'\n\n{}", other, USAGE)),
+    }
+
+    Ok(())
+}
+
+// First line of stdin, so it can be piped in; prompts when run interactively
+fn read_password() -> Result<String> {
+    eprint!("Password: ");
+    std::io::stderr().flush()?;
+    let mut password = String::new();
+    std::io::stdin().lock().read_line(&mut password)?;
+    let password = password.trim_end_matches(['\r', '\n']).to_string();
+    if password.is_empty() {
+        return Err(anyhow!("password must not be empty"));
+    }
+    Ok(password)
+}
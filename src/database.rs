@@ -1,10 +1,12 @@
-use sqlx::{SqlitePool, sqlite::SqliteConnectOptions, Row};
+use sqlx::{SqlitePool, Sqlite, QueryBuilder, sqlite::SqliteConnectOptions, Row};
 use anyhow::Result;
-use chrono::{Utc, DateTime};
+use chrono::{Utc, DateTime, Datelike};
 use uuid::Uuid;
 use std::str::FromStr;
-use crate::models::{Game, CreateGameRequest, UpdateGameRequest};
+use crate::catalog::CatalogEntry;
+use crate::models::{Game, GameFilter, GameListItem, GameFacets, FacetCount, CreateGameRequest, UpdateGameRequest, METADATA_FIELDS, GameManifest, GameFile, GameBuild, CreateBuildRequest, GameMetadata, MetadataMatch, Job, ScheduledTaskRecord, AccessEffect, AccessRule, AccessRuleQuery, CreateAccessRuleRequest, GroupMember, UserGroup};
 
+#[derive(Clone)]
 pub struct Database {
     pool: SqlitePool,
 }
@@ -21,6 +23,22 @@ impl Database {
         Ok(Database { pool })
     }
 
+    // A private, migrated in-memory database. It lives on one connection that never closes,
+    // or the database would go with it.
+    #[cfg(test)]
+    pub async fn in_memory() -> Result<Self> {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await?;
+
+        sqlx::migrate!().run(&pool).await?;
+
+        Ok(Database { pool })
+    }
+
     pub fn get_pool(&self) -> &SqlitePool {
         &self.pool
     }
@@ -42,7 +60,7 @@ impl Database {
             .bind(request.igdb_id)
             .bind(&request.name)
             .bind(&request.file_path)
-            .bind(None::<i64>) // file_size
+            .bind(request.file_size)
             .bind(true) // is_available
             .bind(None::<String>) // added_by
             .bind(now)
@@ -62,41 +80,62 @@ impl Database {
         Ok(game)
     }
 
-    // Get all available games (for store/catalog view)
-    pub async fn get_available_games(&self, page: i64, per_page: i64) -> Result<(Vec<Game>, i64)> {
-        let offset = (page - 1) * per_page;
+    // Get all available games the viewer may see (for store/catalog view)
+    pub async fn get_available_games(&self, viewer: Viewer<'_>, filter: &GameFilter, page: i64, per_page: i64) -> Result<(Vec<GameListItem>, i64)> {
+        self.list_games(filter, GameScope::Available(viewer), page, per_page).await
+    }
 
-        let games = sqlx::query_as::<_, Game>(
-            "SELECT * FROM games WHERE is_available = ? ORDER BY created_at DESC LIMIT ? OFFSET ?"
-        )
-            .bind(true)
-            .bind(per_page)
-            .bind(offset)
-            .fetch_all(&self.pool)
-            .await?;
+    // Whether access rules let the viewer see, install and download a game
+    pub async fn is_game_visible(&self, viewer: Viewer<'_>, game_id: &str) -> Result<bool> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT EXISTS (SELECT 1 FROM games WHERE games.id = ");
+        query.push_bind(game_id.to_string());
+        push_visibility(&mut query, viewer);
+        query.push(")");
 
-        let total = sqlx::query("SELECT COUNT(*) as count FROM games WHERE is_available = ?")
-            .bind(true)
+        let visible = query
+            .build_query_scalar::<bool>()
             .fetch_one(&self.pool)
-            .await?
-            .get::<i64, _>("count");
+            .await?;
 
-        Ok((games, total))
+        Ok(visible)
     }
 
     // Admin-only: Get all games including unavailable ones
-    pub async fn get_games(&self, page: i64, per_page: i64) -> Result<(Vec<Game>, i64)> {
+    pub async fn get_games(&self, filter: &GameFilter, page: i64, per_page: i64) -> Result<(Vec<GameListItem>, i64)> {
+        self.list_games(filter, GameScope::All, page, per_page).await
+    }
+
+    async fn list_games(&self, filter: &GameFilter, scope: GameScope<'_>, page: i64, per_page: i64) -> Result<(Vec<GameListItem>, i64)> {
         let offset = (page - 1) * per_page;
+        let searching = filter.q.as_deref().and_then(fts_match_expression).is_some();
 
-        let games = sqlx::query_as::<_, Game>(
-            "SELECT * FROM games ORDER BY created_at DESC LIMIT ? OFFSET ?"
-        )
-            .bind(per_page)
-            .bind(offset)
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT games.*, ");
+        if searching {
+            // Column weights: game_id, name, summary, storyline, developer, publisher, genres
+            query.push(
+                "bm25(games_fts, 0.0, 10.0, 2.0, 1.0, 3.0, 3.0, 4.0) AS search_rank, \
+                 snippet(games_fts, -1, '<mark>', '</mark>', '…', 12) AS snippet",
+            );
+        } else {
+            query.push("NULL AS search_rank, NULL AS snippet");
+        }
+        push_game_source(&mut query, filter, scope, None);
+        if searching {
+            query.push(" ORDER BY search_rank, games.name");
+        } else {
+            query.push(" ORDER BY games.created_at DESC");
+        }
+        query.push(" LIMIT ").push_bind(per_page).push(" OFFSET ").push_bind(offset);
+
+        let games = query
+            .build_query_as::<GameListItem>()
             .fetch_all(&self.pool)
             .await?;
 
-        let total = sqlx::query("SELECT COUNT(*) as count FROM games")
+        let mut count = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) as count");
+        push_game_source(&mut count, filter, scope, None);
+        let total = count
+            .build()
             .fetch_one(&self.pool)
             .await?
             .get::<i64, _>("count");
@@ -104,104 +143,366 @@ impl Database {
         Ok((games, total))
     }
 
-    #[allow(dead_code)]
-    pub async fn update_game(&self, id: &str, request: UpdateGameRequest) -> Result<Option<Game>> {
+    // Facet counts for a filter sidebar. Each facet ignores its own filter so the
+    // sidebar still lists the alternatives to whatever is currently selected.
+    pub async fn get_game_facets(&self, filter: &GameFilter, scope: GameScope<'_>) -> Result<GameFacets> {
+        Ok(GameFacets {
+            genres: self.json_facet(filter, scope, Facet::Genre, "genres").await?,
+            platforms: self.json_facet(filter, scope, Facet::Platform, "platforms").await?,
+            developers: self.column_facet(filter, scope, Facet::Developer, "games.developer").await?,
+            publishers: self.column_facet(filter, scope, Facet::Publisher, "games.publisher").await?,
+            release_years: self.column_facet(filter, scope, Facet::ReleaseYear, "substr(games.release_date, 1, 4)").await?,
+        })
+    }
+
+    async fn column_facet(&self, filter: &GameFilter, scope: GameScope<'_>, facet: Facet, expression: &str) -> Result<Vec<FacetCount>> {
+        let mut query = QueryBuilder::<Sqlite>::new(format!("SELECT {} AS value, COUNT(*) AS count", expression));
+        push_game_source(&mut query, filter, scope, Some(facet));
+        query.push(format!(" AND {} IS NOT NULL GROUP BY value ORDER BY count DESC, value LIMIT 50", expression));
+
+        let facets = query
+            .build_query_as::<FacetCount>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(facets)
+    }
+
+    async fn json_facet(&self, filter: &GameFilter, scope: GameScope<'_>, facet: Facet, column: &str) -> Result<Vec<FacetCount>> {
+        let mut query = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT value, COUNT(*) AS count FROM ( \
+                SELECT DISTINCT g.id, {} AS value \
+                FROM games g, json_each(CASE WHEN json_valid(g.{column}) THEN g.{column} ELSE '[]' END) j \
+                WHERE g.id IN (SELECT games.id",
+            json_item_name("j"),
+        ));
+        push_game_source(&mut query, filter, scope, Some(facet));
+        query.push(")) WHERE value IS NOT NULL GROUP BY value ORDER BY count DESC, value LIMIT 50");
+
+        let facets = query
+            .build_query_as::<FacetCount>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(facets)
+    }
+
+    pub async fn get_all_games(&self) -> Result<Vec<Game>> {
+        let games = sqlx::query_as::<_, Game>("SELECT * FROM games ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(games)
+    }
+
+    // Write a whole catalog import in one transaction so a bad row leaves nothing half-applied
+    pub async fn apply_catalog_import(&self, creates: &[CatalogEntry], updates: &[CatalogEntry]) -> Result<()> {
         let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
 
-        if let Some(name) = request.name {
-            sqlx::query("UPDATE games SET name = ?, updated_at = ? WHERE id = ?")
-                .bind(name)
+        for entry in creates {
+            sqlx::query(
+                r#"
+                INSERT INTO games (
+                    id, igdb_id, name, summary, storyline, rating, release_date, cover_url,
+                    screenshots, genres, platforms, developer, publisher, file_path, file_size,
+                    is_available, metadata_overrides, metadata_provider, metadata_id,
+                    created_at, updated_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#
+            )
+                .bind(&entry.id)
+                .bind(entry.igdb_id)
+                .bind(&entry.name)
+                .bind(&entry.summary)
+                .bind(&entry.storyline)
+                .bind(entry.rating)
+                .bind(entry.release_date)
+                .bind(&entry.cover_url)
+                .bind(&entry.screenshots)
+                .bind(&entry.genres)
+                .bind(&entry.platforms)
+                .bind(&entry.developer)
+                .bind(&entry.publisher)
+                .bind(&entry.file_path)
+                .bind(entry.file_size)
+                .bind(entry.is_available.unwrap_or(true))
+                .bind(&entry.metadata_overrides)
+                .bind(&entry.metadata_provider)
+                .bind(&entry.metadata_id)
+                .bind(now)
                 .bind(now)
-                .bind(id)
-                .execute(&self.pool)
+                .execute(&mut *tx)
                 .await?;
         }
 
-        if let Some(file_path) = request.file_path {
-            sqlx::query("UPDATE games SET file_path = ?, updated_at = ? WHERE id = ?")
-                .bind(file_path)
+        for entry in updates {
+            sqlx::query(
+                r#"
+                UPDATE games SET
+                    igdb_id = ?, name = ?, summary = ?, storyline = ?, rating = ?, release_date = ?,
+                    cover_url = ?, screenshots = ?, genres = ?, platforms = ?, developer = ?,
+                    publisher = ?, file_path = ?, file_size = ?, is_available = ?,
+                    metadata_overrides = ?, metadata_provider = ?, metadata_id = ?, updated_at = ?
+                WHERE id = ?
+                "#
+            )
+                .bind(entry.igdb_id)
+                .bind(&entry.name)
+                .bind(&entry.summary)
+                .bind(&entry.storyline)
+                .bind(entry.rating)
+                .bind(entry.release_date)
+                .bind(&entry.cover_url)
+                .bind(&entry.screenshots)
+                .bind(&entry.genres)
+                .bind(&entry.platforms)
+                .bind(&entry.developer)
+                .bind(&entry.publisher)
+                .bind(&entry.file_path)
+                .bind(entry.file_size)
+                .bind(entry.is_available.unwrap_or(true))
+                .bind(&entry.metadata_overrides)
+                .bind(&entry.metadata_provider)
+                .bind(&entry.metadata_id)
                 .bind(now)
-                .bind(id)
-                .execute(&self.pool)
+                .bind(&entry.id)
+                .execute(&mut *tx)
                 .await?;
         }
 
-        self.get_game_by_id(id).await
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // Library scanner support: every game that points at something on disk
+    pub async fn get_games_with_files(&self) -> Result<Vec<Game>> {
+        let games = sqlx::query_as::<_, Game>("SELECT * FROM games WHERE file_path IS NOT NULL")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(games)
     }
 
-    #[allow(dead_code)]
-    pub async fn delete_game(&self, id: &str) -> Result<bool> {
-        let result = sqlx::query("DELETE FROM games WHERE id = ?")
+    pub async fn update_game_file_size(&self, id: &str, file_size: Option<i64>) -> Result<()> {
+        sqlx::query("UPDATE games SET file_size = ?, updated_at = ? WHERE id = ?")
+            .bind(file_size)
+            .bind(Utc::now())
             .bind(id)
             .execute(&self.pool)
             .await?;
 
-        Ok(result.rows_affected() > 0)
+        Ok(())
+    }
+
+    // The scanner hides games whose files vanished and brings back the ones it hid
+    pub async fn set_game_missing_on_disk(&self, id: &str, file_size: Option<i64>, missing: bool) -> Result<()> {
+        sqlx::query("UPDATE games SET file_size = ?, is_available = ?, missing_on_disk = ?, updated_at = ? WHERE id = ?")
+            .bind(file_size)
+            .bind(!missing)
+            .bind(missing)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
     }
 
-    pub async fn update_game_metadata(&self, id: &str, igdb_game: &crate::models::IgdbGame) -> Result<()> {
+    // Apply a partial update atomically. Metadata fields touched here become overrides so a
+    // later metadata refresh does not undo the admin's edit.
+    pub async fn update_game(&self, id: &str, request: UpdateGameRequest) -> Result<Option<Game>> {
+        let mut tx = self.pool.begin().await?;
+
+        let current = sqlx::query_as::<_, Game>("SELECT * FROM games WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        let current = match current {
+            Some(game) => game,
+            None => return Ok(None),
+        };
+
+        let mut overrides = match &request.metadata_overrides {
+            Some(fields) => fields.iter().filter(|f| METADATA_FIELDS.contains(&f.as_str())).cloned().collect(),
+            None => parse_overrides(current.metadata_overrides.as_deref()),
+        };
+        let mut touched: Vec<&str> = Vec::new();
+
+        let mut query = QueryBuilder::<Sqlite>::new("UPDATE games SET updated_at = ");
+        query.push_bind(Utc::now());
+
+        macro_rules! set_field {
+            ($column:literal, $value:expr) => {
+                if let Some(value) = $value {
+                    query.push(concat!(", ", $column, " = ")).push_bind(value);
+                    if METADATA_FIELDS.contains(&$column) {
+                        touched.push($column);
+                    }
+                }
+            };
+        }
+
+        set_field!("name", request.name);
+        set_field!("igdb_id", request.igdb_id);
+        set_field!("summary", request.summary);
+        set_field!("storyline", request.storyline);
+        set_field!("rating", request.rating);
+        set_field!("release_date", request.release_date);
+        set_field!("cover_url", request.cover_url);
+        set_field!("screenshots", request.screenshots.map(|urls| urls.map(|urls| serde_json::to_string(&urls).unwrap_or_default())));
+        set_field!("genres", request.genres.map(|names| names.map(|names| named_list_json(&names))));
+        set_field!("platforms", request.platforms.map(|names| names.map(|names| named_list_json(&names))));
+        set_field!("developer", request.developer);
+        set_field!("publisher", request.publisher);
+        set_field!("file_path", request.file_path);
+        set_field!("file_size", request.file_size);
+        set_field!("is_available", request.is_available);
+        // The admin's choice stands; the scanner will not bring the game back on its own
+        if request.is_available.is_some() {
+            query.push(", missing_on_disk = FALSE");
+        }
+        // An id only means something at the provider that issued it, so switching providers
+        // without naming the new id unlinks the game rather than relabelling the old one
+        let provider_changed = request
+            .metadata_provider
+            .as_ref()
+            .is_some_and(|provider| *provider != current.metadata_provider);
+        if provider_changed && request.metadata_id.is_none() {
+            query.push(", metadata_id = NULL, metadata_updated_at = NULL");
+        }
+        set_field!("metadata_provider", request.metadata_provider);
+        set_field!("metadata_id", request.metadata_id);
+
+        for field in touched {
+            if !overrides.iter().any(|f| f == field) {
+                overrides.push(field.to_string());
+            }
+        }
+        let overrides = if overrides.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&overrides)?)
+        };
+        query.push(", metadata_overrides = ").push_bind(overrides);
+
+        query.push(" WHERE id = ").push_bind(id.to_string()).push(" RETURNING *");
+
+        let game = query
+            .build_query_as::<Game>()
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(game)
+    }
+
+    // Returns how many user libraries referenced the game, or None if it did not exist
+    pub async fn delete_game(&self, id: &str) -> Result<Option<i64>> {
+        let mut tx = self.pool.begin().await?;
+
+        let affected_libraries = sqlx::query("SELECT COUNT(*) as count FROM user_games WHERE game_id = ?")
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?
+            .get::<i64, _>("count");
+
+        let result = sqlx::query("DELETE FROM games WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        tx.commit().await?;
+
+        Ok(Some(affected_libraries))
+    }
+
+    // Apply fetched metadata and remember where it came from. Fields the admin has
+    // overridden are left untouched.
+    pub async fn update_game_metadata(&self, id: &str, metadata: &GameMetadata) -> Result<()> {
         let now = Utc::now();
 
-        let release_date = igdb_game.first_release_date.and_then(|timestamp| {
-            DateTime::from_timestamp(timestamp, 0)
-        });
-
-        let cover_url = igdb_game.cover.as_ref().map(|cover| {
-            format!("https:{}", cover.url.replace("t_thumb", "t_cover_big"))
-        });
-
-        let screenshots = igdb_game.screenshots.as_ref().map(|screenshots| {
-            serde_json::to_string(&screenshots.iter()
-                .map(|s| format!("https:{}", s.url.replace("t_thumb", "t_screenshot_med")))
-                .collect::<Vec<_>>())
-                .unwrap_or_default()
-        });
-
-        let genres = igdb_game.genres.as_ref().map(|genres| {
-            serde_json::to_string(genres).unwrap_or_default()
-        });
-
-        let platforms = igdb_game.platforms.as_ref().map(|platforms| {
-            serde_json::to_string(platforms).unwrap_or_default()
-        });
-
-        let developer = igdb_game.involved_companies.as_ref().and_then(|companies| {
-            companies.iter()
-                .find(|c| c.developer)
-                .map(|c| c.company.name.clone())
-        });
-
-        let publisher = igdb_game.involved_companies.as_ref().and_then(|companies| {
-            companies.iter()
-                .find(|c| c.publisher)
-                .map(|c| c.company.name.clone())
-        });
+        let screenshots = (!metadata.screenshots.is_empty())
+            .then(|| serde_json::to_string(&metadata.screenshots))
+            .transpose()?;
+        let genres = (!metadata.genres.is_empty()).then(|| named_list_json(&metadata.genres));
+        let platforms = (!metadata.platforms.is_empty()).then(|| named_list_json(&metadata.platforms));
 
-        sqlx::query(
+        let overrides = sqlx::query("SELECT metadata_overrides FROM games WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .map(|row| parse_overrides(row.get::<Option<String>, _>("metadata_overrides").as_deref()))
+            .unwrap_or_default();
+
+        let mut query = QueryBuilder::<Sqlite>::new("UPDATE games SET updated_at = ");
+        query.push_bind(now);
+
+        macro_rules! set_field {
+            ($column:literal, $value:expr) => {
+                if !overrides.iter().any(|f| f == $column) {
+                    query.push(concat!(", ", $column, " = ")).push_bind($value);
+                }
+            };
+        }
+
+        set_field!("summary", metadata.summary.clone());
+        set_field!("storyline", metadata.storyline.clone());
+        set_field!("rating", metadata.rating);
+        set_field!("release_date", metadata.release_date);
+        set_field!("cover_url", metadata.cover_url.clone());
+        set_field!("screenshots", screenshots);
+        set_field!("genres", genres);
+        set_field!("platforms", platforms);
+        set_field!("developer", metadata.developer.clone());
+        set_field!("publisher", metadata.publisher.clone());
+
+        query.push(", metadata_updated_at = ").push_bind(now);
+        query.push(", metadata_provider = ").push_bind(metadata.provider.clone());
+        query.push(", metadata_id = ").push_bind(metadata.external_id.clone());
+        if metadata.provider == "igdb" {
+            if let Ok(igdb_id) = metadata.external_id.parse::<i64>() {
+                query.push(", igdb_id = ").push_bind(igdb_id);
+            }
+        }
+
+        query.push(" WHERE id = ").push_bind(id.to_string());
+        query.build().execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    // Swap in mirrored artwork URLs, but only if nobody changed the artwork in the meantime
+    pub async fn set_game_media(
+        &self,
+        id: &str,
+        expected: (Option<&str>, Option<&str>),
+        cover_url: Option<String>,
+        screenshots: Option<String>,
+    ) -> Result<bool> {
+        let result = sqlx::query(
             r#"
-            UPDATE games SET
-                summary = ?, storyline = ?, rating = ?, release_date = ?,
-                cover_url = ?, screenshots = ?, genres = ?, platforms = ?,
-                developer = ?, publisher = ?, updated_at = ?
-            WHERE id = ?
+            UPDATE games SET cover_url = ?, screenshots = ?, updated_at = ?
+            WHERE id = ? AND cover_url IS ? AND screenshots IS ?
             "#
         )
-            .bind(&igdb_game.summary)
-            .bind(&igdb_game.storyline)
-            .bind(igdb_game.rating)
-            .bind(release_date)
             .bind(cover_url)
             .bind(screenshots)
-            .bind(genres)
-            .bind(platforms)
-            .bind(developer)
-            .bind(publisher)
-            .bind(now)
+            .bind(Utc::now())
             .bind(id)
+            .bind(expected.0)
+            .bind(expected.1)
             .execute(&self.pool)
             .await?;
 
-        Ok(())
+        Ok(result.rows_affected() > 0)
     }
 
     // User game library methods
@@ -224,12 +525,13 @@ impl Database {
         // Insert or update user_games record
         let result = sqlx::query(
             r#"
-            INSERT INTO user_games (id, user_id, game_id, is_installed, install_path, installed_at, created_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO user_games (id, user_id, game_id, is_installed, install_path, installed_at, created_at, installed_build_id)
+            VALUES (?, ?, ?, ?, ?, ?, ?, (SELECT current_build_id FROM games WHERE id = ?))
             ON CONFLICT(user_id, game_id) DO UPDATE SET
                 is_installed = excluded.is_installed,
                 install_path = excluded.install_path,
-                installed_at = excluded.installed_at
+                installed_at = excluded.installed_at,
+                installed_build_id = excluded.installed_build_id
             "#
         )
             .bind(&user_game_id)
@@ -239,6 +541,7 @@ impl Database {
             .bind(install_path)
             .bind(now)
             .bind(now)
+            .bind(game_id)
             .execute(&self.pool)
             .await?;
 
@@ -259,10 +562,11 @@ impl Database {
         Ok(result.rows_affected() > 0)
     }
 
-    pub async fn get_user_library(&self, user_id: &str, page: i64, per_page: i64) -> Result<(Vec<UserGameWithDetails>, i64)> {
+    // Games the viewer has lost access to stay in user_games but are left out
+    pub async fn get_user_library(&self, viewer: Viewer<'_>, filter: &GameFilter, page: i64, per_page: i64) -> Result<(Vec<UserGameWithDetails>, i64)> {
         let offset = (page - 1) * per_page;
 
-        let user_games = sqlx::query_as::<_, UserGameWithDetails>(
+        let mut query = QueryBuilder::<Sqlite>::new(
             r#"
             SELECT
                 ug.id as user_game_id,
@@ -271,6 +575,7 @@ impl Database {
                 ug.installed_at,
                 ug.last_played,
                 ug.play_time_minutes,
+                ug.installed_build_id,
                 g.id,
                 g.igdb_id,
                 g.name,
@@ -289,24 +594,29 @@ impl Database {
                 g.is_available,
                 g.added_by,
                 g.created_at,
-                g.updated_at
+                g.updated_at,
+                g.current_build_id
             FROM user_games ug
             JOIN games g ON ug.game_id = g.id
-            WHERE ug.user_id = ?
-            ORDER BY ug.created_at DESC
-            LIMIT ? OFFSET ?
-            "#
-        )
-            .bind(user_id)
-            .bind(per_page)
-            .bind(offset)
+            WHERE ug.user_id = "#
+        );
+        query.push_bind(viewer.user_id.to_string()).push(" AND ug.game_id IN (SELECT games.id");
+        push_game_source(&mut query, filter, GameScope::Library(viewer), None);
+        query
+            .push(") ORDER BY ug.created_at DESC LIMIT ")
+            .push_bind(per_page)
+            .push(" OFFSET ")
+            .push_bind(offset);
+
+        let user_games = query
+            .build_query_as::<UserGameWithDetails>()
             .fetch_all(&self.pool)
             .await?;
 
-        let total = sqlx::query(
-            "SELECT COUNT(*) as count FROM user_games WHERE user_id = ?"
-        )
-            .bind(user_id)
+        let mut count = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) as count");
+        push_game_source(&mut count, filter, GameScope::Library(viewer), None);
+        let total = count
+            .build()
             .fetch_one(&self.pool)
             .await?
             .get::<i64, _>("count");
@@ -324,6 +634,7 @@ impl Database {
                 ug.installed_at,
                 ug.last_played,
                 ug.play_time_minutes,
+                ug.installed_build_id,
                 g.id,
                 g.igdb_id,
                 g.name,
@@ -342,7 +653,8 @@ impl Database {
                 g.is_available,
                 g.added_by,
                 g.created_at,
-                g.updated_at
+                g.updated_at,
+                g.current_build_id
             FROM user_games ug
             JOIN games g ON ug.game_id = g.id
             WHERE ug.user_id = ? AND ug.game_id = ?
@@ -376,50 +688,1129 @@ impl Database {
 
         Ok(result.rows_affected() > 0)
     }
-}
 
-// New struct for user game details
-#[derive(Debug, sqlx::FromRow)]
-pub struct UserGameWithDetails {
-    pub user_game_id: String,
-    pub is_installed: bool,
-    pub install_path: Option<String>,
-    pub installed_at: Option<DateTime<Utc>>,
-    pub last_played: Option<DateTime<Utc>>,
-    pub play_time_minutes: i64,
+    // Game build methods
+    pub async fn get_game_builds(&self, game_id: &str) -> Result<Vec<GameBuild>> {
+        let builds = sqlx::query_as::<_, GameBuild>(
+            "SELECT * FROM game_builds WHERE game_id = ? ORDER BY created_at DESC"
+        )
+            .bind(game_id)
+            .fetch_all(&self.pool)
+            .await?;
 
-    // Game details (flattened from games table)
-    pub id: String,
-    #[allow(dead_code)]
-    pub igdb_id: Option<i32>,
-    pub name: String,
-    pub summary: Option<String>,
-    #[allow(dead_code)]
-    pub storyline: Option<String>,
-    #[allow(dead_code)]
-    pub rating: Option<f64>,
-    #[allow(dead_code)]
-    pub release_date: Option<DateTime<Utc>>,
-    #[allow(dead_code)]
-    pub cover_url: Option<String>,
-    #[allow(dead_code)]
-    pub screenshots: Option<String>,
-    #[allow(dead_code)]
-    pub genres: Option<String>,
-    #[allow(dead_code)]
-    pub platforms: Option<String>,
-    pub developer: Option<String>,
-    pub publisher: Option<String>,
-    #[allow(dead_code)]
-    pub file_path: Option<String>,
-    #[allow(dead_code)]
-    pub file_size: Option<i64>,
-    #[allow(dead_code)]
-    pub is_available: bool,
-    #[allow(dead_code)]
-    pub added_by: Option<String>,
-    #[allow(dead_code)]
-    pub created_at: DateTime<Utc>,
-    #[allow(dead_code)]
-    pub updated_at: DateTime<Utc>,
+        Ok(builds)
+    }
+
+    pub async fn create_game_build(&self, game_id: &str, request: &CreateBuildRequest, file_size: Option<i64>) -> Result<GameBuild> {
+        let build = sqlx::query_as::<_, GameBuild>(
+            r#"
+            INSERT INTO game_builds (id, game_id, version, release_notes, file_path, file_size, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#
+        )
+            .bind(Uuid::new_v4().to_string())
+            .bind(game_id)
+            .bind(&request.version)
+            .bind(&request.release_notes)
+            .bind(&request.file_path)
+            .bind(file_size)
+            .bind(Utc::now())
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(build)
+    }
+
+    // Games the scanner imported from a file before it was registered as a build of `game_id`.
+    // Their library entries move over and the duplicates are deleted; a game with builds of
+    // its own is left for an admin to sort out. Returns the ids of the deleted games.
+    pub async fn absorb_duplicate_games(&self, game_id: &str, file_path: &str) -> Result<Vec<String>> {
+        let mut tx = self.pool.begin().await?;
+
+        let duplicates: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT id FROM games
+            WHERE file_path = ? AND id != ?
+            AND NOT EXISTS (SELECT 1 FROM game_builds WHERE game_builds.game_id = games.id)
+            "#
+        )
+            .bind(file_path)
+            .bind(game_id)
+            .fetch_all(&mut *tx)
+            .await?;
+
+        for duplicate in &duplicates {
+            // Users who already have both keep their existing entry
+            sqlx::query("UPDATE OR IGNORE user_games SET game_id = ? WHERE game_id = ?")
+                .bind(game_id)
+                .bind(duplicate)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM games WHERE id = ?")
+                .bind(duplicate)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(duplicates)
+    }
+
+    // Point the game at a build; its file_path/file_size follow so downloads serve the new version
+    pub async fn set_current_build(&self, game_id: &str, build_id: &str) -> Result<Option<Game>> {
+        let mut tx = self.pool.begin().await?;
+
+        let build = sqlx::query_as::<_, GameBuild>("SELECT * FROM game_builds WHERE id = ? AND game_id = ?")
+            .bind(build_id)
+            .bind(game_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        let build = match build {
+            Some(build) => build,
+            None => return Ok(None),
+        };
+
+        let game = sqlx::query_as::<_, Game>(
+            r#"
+            UPDATE games SET current_build_id = ?, file_path = ?, file_size = ?, updated_at = ?
+            WHERE id = ?
+            RETURNING *
+            "#
+        )
+            .bind(&build.id)
+            .bind(&build.file_path)
+            .bind(build.file_size)
+            .bind(Utc::now())
+            .bind(game_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(game)
+    }
+
+    pub async fn get_build_file_paths(&self) -> Result<Vec<String>> {
+        let paths = sqlx::query("SELECT file_path FROM game_builds")
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|row| row.get::<String, _>("file_path"))
+            .collect();
+
+        Ok(paths)
+    }
+
+    // Game manifest methods
+    pub async fn get_manifest(&self, game_id: &str) -> Result<Option<GameManifest>> {
+        let manifest = sqlx::query_as::<_, GameManifest>("SELECT * FROM game_manifests WHERE game_id = ?")
+            .bind(game_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(manifest)
+    }
+
+    pub async fn get_all_manifests(&self) -> Result<Vec<GameManifest>> {
+        let manifests = sqlx::query_as::<_, GameManifest>("SELECT * FROM game_manifests")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(manifests)
+    }
+
+    pub async fn get_manifest_files(&self, game_id: &str) -> Result<Vec<GameFile>> {
+        let files = sqlx::query_as::<_, GameFile>(
+            "SELECT * FROM game_files WHERE game_id = ? ORDER BY relative_path"
+        )
+            .bind(game_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(files)
+    }
+
+    pub async fn get_manifest_file(&self, game_id: &str, relative_path: &str) -> Result<Option<GameFile>> {
+        let file = sqlx::query_as::<_, GameFile>(
+            "SELECT * FROM game_files WHERE game_id = ? AND relative_path = ?"
+        )
+            .bind(game_id)
+            .bind(relative_path)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(file)
+    }
+
+    pub async fn set_manifest_status(&self, game_id: &str, status: &str, error: Option<String>) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO game_manifests (game_id, status, error, updated_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(game_id) DO UPDATE SET
+                status = excluded.status,
+                error = excluded.error,
+                updated_at = excluded.updated_at
+            "#
+        )
+            .bind(game_id)
+            .bind(status)
+            .bind(error)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Swap the whole file list in one transaction so readers never see a half-written manifest
+    pub async fn replace_manifest(&self, game_id: &str, files: &[GameFile], fingerprint: &str) -> Result<()> {
+        let now = Utc::now();
+        let total_size: i64 = files.iter().map(|f| f.size).sum();
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM game_files WHERE game_id = ?")
+            .bind(game_id)
+            .execute(&mut *tx)
+            .await?;
+
+        for file in files {
+            sqlx::query(
+                r#"
+                INSERT INTO game_files (id, game_id, relative_path, size, sha256, modified_at)
+                VALUES (?, ?, ?, ?, ?, ?)
+                "#
+            )
+                .bind(&file.id)
+                .bind(&file.game_id)
+                .bind(&file.relative_path)
+                .bind(file.size)
+                .bind(&file.sha256)
+                .bind(file.modified_at)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO game_manifests (game_id, status, file_count, total_size, error, generated_at, updated_at, fingerprint)
+            VALUES (?, 'ready', ?, ?, NULL, ?, ?, ?)
+            ON CONFLICT(game_id) DO UPDATE SET
+                status = excluded.status,
+                file_count = excluded.file_count,
+                total_size = excluded.total_size,
+                error = NULL,
+                generated_at = excluded.generated_at,
+                updated_at = excluded.updated_at,
+                fingerprint = excluded.fingerprint
+            "#
+        )
+            .bind(game_id)
+            .bind(files.len() as i64)
+            .bind(total_size)
+            .bind(now)
+            .bind(now)
+            .bind(fingerprint)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // Metadata match review queue
+    pub async fn get_unlinked_game_ids(&self) -> Result<Vec<String>> {
+        let ids = sqlx::query("SELECT id FROM games WHERE metadata_id IS NULL AND igdb_id IS NULL ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|row| row.get("id"))
+            .collect();
+
+        Ok(ids)
+    }
+
+    // Candidates already on record (including rejected ones) are kept as they are
+    pub async fn record_match_candidates(&self, game_id: &str, candidates: &[(GameMetadata, f64)], status: &str) -> Result<usize> {
+        let now = Utc::now();
+        let resolved_at = (status != "pending").then_some(now);
+        let mut tx = self.pool.begin().await?;
+        let mut recorded = 0;
+
+        for (candidate, score) in candidates {
+            let result = sqlx::query(
+                r#"
+                INSERT INTO metadata_matches (
+                    id, game_id, provider, external_id, candidate_name, release_year, score,
+                    metadata, status, created_at, resolved_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT(game_id, provider, external_id) DO NOTHING
+                "#
+            )
+                .bind(Uuid::new_v4().to_string())
+                .bind(game_id)
+                .bind(&candidate.provider)
+                .bind(&candidate.external_id)
+                .bind(&candidate.name)
+                .bind(candidate.release_date.map(|date| date.year()))
+                .bind(score)
+                .bind(serde_json::to_string(candidate)?)
+                .bind(status)
+                .bind(now)
+                .bind(resolved_at)
+                .execute(&mut *tx)
+                .await?;
+            recorded += result.rows_affected() as usize;
+        }
+
+        tx.commit().await?;
+
+        Ok(recorded)
+    }
+
+    pub async fn get_metadata_matches(&self, status: Option<&str>, game_id: Option<&str>) -> Result<Vec<MetadataMatch>> {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "SELECT m.*, g.name as game_name FROM metadata_matches m JOIN games g ON g.id = m.game_id WHERE 1 = 1",
+        );
+        if let Some(status) = status {
+            query.push(" AND m.status = ").push_bind(status.to_string());
+        }
+        if let Some(game_id) = game_id {
+            query.push(" AND m.game_id = ").push_bind(game_id.to_string());
+        }
+        query.push(" ORDER BY g.name, m.score DESC");
+
+        let matches = query
+            .build_query_as::<MetadataMatch>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(matches)
+    }
+
+    pub async fn get_metadata_match(&self, id: &str) -> Result<Option<MetadataMatch>> {
+        let found = sqlx::query_as::<_, MetadataMatch>(
+            "SELECT m.*, g.name as game_name FROM metadata_matches m JOIN games g ON g.id = m.game_id WHERE m.id = ?",
+        )
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(found)
+    }
+
+    // Accepting one candidate rejects the game's other pending candidates.
+    // Returns false if the match was no longer pending.
+    pub async fn resolve_metadata_match(&self, id: &str, accept: bool, user_id: &str) -> Result<bool> {
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+
+        let game_id = sqlx::query(
+            r#"
+            UPDATE metadata_matches SET status = ?, resolved_by = ?, resolved_at = ?
+            WHERE id = ? AND status = 'pending'
+            RETURNING game_id
+            "#
+        )
+            .bind(if accept { "accepted" } else { "rejected" })
+            .bind(user_id)
+            .bind(now)
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .map(|row| row.get::<String, _>("game_id"));
+
+        let game_id = match game_id {
+            Some(game_id) => game_id,
+            None => return Ok(false),
+        };
+
+        if accept {
+            sqlx::query(
+                r#"
+                UPDATE metadata_matches SET status = 'rejected', resolved_by = ?, resolved_at = ?
+                WHERE game_id = ? AND status = 'pending'
+                "#
+            )
+                .bind(user_id)
+                .bind(now)
+                .bind(&game_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    // Background jobs
+    pub async fn create_job(&self, kind: &str, payload: &str, max_attempts: i64, created_by: Option<&str>) -> Result<Job> {
+        let now = Utc::now();
+
+        // One statement rather than a read-then-write transaction, which SQLite cannot
+        // upgrade to a write lock while workers are busy updating other jobs
+        let inserted = sqlx::query_as::<_, Job>(
+            r#"
+            INSERT INTO jobs (id, kind, payload, status, max_attempts, created_by, run_after, created_at, updated_at)
+            SELECT ?, ?, ?, 'queued', ?, ?, ?, ?, ?
+            WHERE NOT EXISTS (SELECT 1 FROM jobs WHERE payload = ? AND status = 'queued')
+            RETURNING *
+            "#
+        )
+            .bind(Uuid::new_v4().to_string())
+            .bind(kind)
+            .bind(payload)
+            .bind(max_attempts)
+            .bind(created_by)
+            .bind(now)
+            .bind(now)
+            .bind(now)
+            .bind(payload)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        if let Some(job) = inserted {
+            return Ok(job);
+        }
+
+        let waiting = sqlx::query_as::<_, Job>(
+            "SELECT * FROM jobs WHERE payload = ? AND status = 'queued' ORDER BY created_at LIMIT 1",
+        )
+            .bind(payload)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        waiting.ok_or_else(|| anyhow::anyhow!("queued job disappeared while enqueuing"))
+    }
+
+    // Atomically move the oldest due job to running. A job identical to one already running
+    // waits for it to finish: create_job only merges queued duplicates, because work queued
+    // mid-run may have to see changes the running job missed, but the two must never overlap.
+    pub async fn claim_next_job(&self) -> Result<Option<Job>> {
+        let now = Utc::now();
+
+        let job = sqlx::query_as::<_, Job>(
+            r#"
+            UPDATE jobs SET status = 'running', attempts = attempts + 1, progress = 0, message = NULL,
+                started_at = ?, updated_at = ?
+            WHERE id = (
+                SELECT id FROM jobs WHERE status = 'queued' AND run_after <= ?
+                AND NOT EXISTS (
+                    SELECT 1 FROM jobs AS running WHERE running.status = 'running' AND running.payload = jobs.payload
+                )
+                ORDER BY run_after, created_at LIMIT 1
+            )
+            RETURNING *
+            "#
+        )
+            .bind(now)
+            .bind(now)
+            .bind(now)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(job)
+    }
+
+    pub async fn update_job_progress(&self, id: &str, progress: f64, message: Option<&str>) -> Result<()> {
+        sqlx::query("UPDATE jobs SET progress = ?, message = COALESCE(?, message), updated_at = ? WHERE id = ? AND status = 'running'")
+            .bind(progress)
+            .bind(message)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn finish_job(&self, id: &str, status: &str, result: Option<&str>, error: Option<&str>) -> Result<()> {
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            UPDATE jobs SET status = ?, result = ?, error = COALESCE(?, error),
+                progress = CASE WHEN ? = 'completed' THEN 100 ELSE progress END,
+                finished_at = ?, updated_at = ?
+            WHERE id = ?
+            "#
+        )
+            .bind(status)
+            .bind(result)
+            .bind(error)
+            .bind(status)
+            .bind(now)
+            .bind(now)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn retry_job(&self, id: &str, error: &str, delay: std::time::Duration) -> Result<()> {
+        let now = Utc::now();
+
+        sqlx::query("UPDATE jobs SET status = 'queued', error = ?, run_after = ?, updated_at = ? WHERE id = ?")
+            .bind(error)
+            .bind(now + chrono::Duration::from_std(delay)?)
+            .bind(now)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Queued jobs are cancelled outright; running ones are flagged and stop at their next check
+    pub async fn request_job_cancel(&self, id: &str) -> Result<Option<Job>> {
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            UPDATE jobs SET
+                status = CASE WHEN status = 'queued' THEN 'cancelled' ELSE status END,
+                finished_at = CASE WHEN status = 'queued' THEN ? ELSE finished_at END,
+                cancel_requested = TRUE,
+                updated_at = ?
+            WHERE id = ? AND status IN ('queued', 'running')
+            "#
+        )
+            .bind(now)
+            .bind(now)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        self.get_job(id).await
+    }
+
+    pub async fn requeue_interrupted_jobs(&self) -> Result<u64> {
+        let result = sqlx::query(
+            "UPDATE jobs SET status = CASE WHEN cancel_requested THEN 'cancelled' ELSE 'queued' END, updated_at = ? WHERE status = 'running'",
+        )
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn get_job(&self, id: &str) -> Result<Option<Job>> {
+        let job = sqlx::query_as::<_, Job>("SELECT * FROM jobs WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(job)
+    }
+
+    pub async fn get_jobs(&self, status: Option<&str>, kind: Option<&str>, limit: i64) -> Result<Vec<Job>> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM jobs WHERE 1 = 1");
+        if let Some(status) = status {
+            query.push(" AND status = ").push_bind(status.to_string());
+        }
+        if let Some(kind) = kind {
+            query.push(" AND kind = ").push_bind(kind.to_string());
+        }
+        query.push(" ORDER BY created_at DESC LIMIT ").push_bind(limit);
+
+        let jobs = query.build_query_as::<Job>().fetch_all(&self.pool).await?;
+
+        Ok(jobs)
+    }
+
+    pub async fn purge_finished_jobs(&self, older_than: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query(
+            "DELETE FROM jobs WHERE status IN ('completed', 'failed', 'cancelled') AND finished_at < ?",
+        )
+            .bind(older_than)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // Linked games whose metadata was last fetched before the cutoff (or never recorded)
+    pub async fn get_stale_metadata_game_ids(&self, older_than: DateTime<Utc>) -> Result<Vec<String>> {
+        let ids = sqlx::query(
+            r#"
+            SELECT id FROM games
+            WHERE (metadata_id IS NOT NULL OR igdb_id IS NOT NULL)
+              AND (metadata_updated_at IS NULL OR metadata_updated_at < ?)
+            ORDER BY metadata_updated_at
+            "#
+        )
+            .bind(older_than)
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|row| row.get("id"))
+            .collect();
+
+        Ok(ids)
+    }
+
+    // Merge the search index segments, refresh planner statistics and reclaim free pages
+    pub async fn optimize(&self) -> Result<()> {
+        sqlx::query("INSERT INTO games_fts(games_fts) VALUES('optimize')")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("PRAGMA optimize").execute(&self.pool).await?;
+        sqlx::query("VACUUM").execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    // Scheduled maintenance tasks
+    pub async fn get_scheduled_tasks(&self) -> Result<Vec<ScheduledTaskRecord>> {
+        let tasks = sqlx::query_as::<_, ScheduledTaskRecord>("SELECT * FROM scheduled_tasks")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(tasks)
+    }
+
+    pub async fn get_scheduled_task(&self, name: &str) -> Result<Option<ScheduledTaskRecord>> {
+        let task = sqlx::query_as::<_, ScheduledTaskRecord>("SELECT * FROM scheduled_tasks WHERE name = ?")
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(task)
+    }
+
+    pub async fn set_scheduled_task_next_run(&self, name: &str, next_run_at: DateTime<Utc>) -> Result<()> {
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO scheduled_tasks (name, next_run_at, updated_at) VALUES (?, ?, ?)
+            ON CONFLICT(name) DO UPDATE SET next_run_at = excluded.next_run_at, updated_at = excluded.updated_at
+            "#
+        )
+            .bind(name)
+            .bind(next_run_at)
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn start_scheduled_task(&self, name: &str, started_at: DateTime<Utc>) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO scheduled_tasks (name, last_status, last_started_at, updated_at) VALUES (?, 'running', ?, ?)
+            ON CONFLICT(name) DO UPDATE SET
+                last_status = 'running',
+                last_message = NULL,
+                last_started_at = excluded.last_started_at,
+                last_finished_at = NULL,
+                updated_at = excluded.updated_at
+            "#
+        )
+            .bind(name)
+            .bind(started_at)
+            .bind(started_at)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn finish_scheduled_task(&self, name: &str, status: &str, message: &str) -> Result<()> {
+        let now = Utc::now();
+
+        sqlx::query(
+            "UPDATE scheduled_tasks SET last_status = ?, last_message = ?, last_finished_at = ?, updated_at = ? WHERE name = ?",
+        )
+            .bind(status)
+            .bind(message)
+            .bind(now)
+            .bind(now)
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // A task still marked running was cut off by a shutdown
+    pub async fn fail_interrupted_scheduled_tasks(&self) -> Result<()> {
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            UPDATE scheduled_tasks SET last_status = 'failed', last_message = 'interrupted by shutdown',
+                last_finished_at = ?, updated_at = ?
+            WHERE last_status = 'running'
+            "#
+        )
+            .bind(now)
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // IGDB response cache
+    pub async fn get_igdb_cache(&self, cache_key: &str) -> Result<Option<(String, DateTime<Utc>)>> {
+        let row = sqlx::query("SELECT response, fetched_at FROM igdb_cache WHERE cache_key = ?")
+            .bind(cache_key)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| (row.get("response"), row.get("fetched_at"))))
+    }
+
+    pub async fn put_igdb_cache(&self, cache_key: &str, response: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO igdb_cache (cache_key, response, fetched_at) VALUES (?, ?, ?)
+            ON CONFLICT(cache_key) DO UPDATE SET
+                response = excluded.response,
+                fetched_at = excluded.fetched_at
+            "#
+        )
+            .bind(cache_key)
+            .bind(response)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Deletes everything, or only entries fetched before `older_than`
+    pub async fn purge_igdb_cache(&self, older_than: Option<DateTime<Utc>>) -> Result<u64> {
+        let result = match older_than {
+            Some(cutoff) => {
+                sqlx::query("DELETE FROM igdb_cache WHERE fetched_at < ?")
+                    .bind(cutoff)
+                    .execute(&self.pool)
+                    .await?
+            }
+            None => sqlx::query("DELETE FROM igdb_cache").execute(&self.pool).await?,
+        };
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn get_groups(&self) -> Result<Vec<UserGroup>> {
+        let groups = sqlx::query_as::<_, UserGroup>(
+            r#"
+            SELECT g.*, (SELECT COUNT(*) FROM user_group_members m WHERE m.group_id = g.id) AS member_count
+            FROM user_groups g
+            ORDER BY g.name
+            "#
+        )
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(groups)
+    }
+
+    pub async fn get_group(&self, id: &str) -> Result<Option<UserGroup>> {
+        let group = sqlx::query_as::<_, UserGroup>(
+            r#"
+            SELECT g.*, (SELECT COUNT(*) FROM user_group_members m WHERE m.group_id = g.id) AS member_count
+            FROM user_groups g
+            WHERE g.id = ?
+            "#
+        )
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(group)
+    }
+
+    // None when a group with that name already exists
+    pub async fn create_group(&self, name: &str, description: Option<&str>) -> Result<Option<UserGroup>> {
+        let group = sqlx::query_as::<_, UserGroup>(
+            r#"
+            INSERT INTO user_groups (id, name, description, created_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(name) DO NOTHING
+            RETURNING *, 0 AS member_count
+            "#
+        )
+            .bind(Uuid::new_v4().to_string())
+            .bind(name)
+            .bind(description)
+            .bind(Utc::now())
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(group)
+    }
+
+    // Memberships and rules for the group go with it
+    pub async fn delete_group(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM user_groups WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_group_members(&self, group_id: &str) -> Result<Vec<GroupMember>> {
+        let members = sqlx::query_as::<_, GroupMember>(
+            r#"
+            SELECT m.user_id, u.username, m.added_at
+            FROM user_group_members m
+            JOIN users u ON u.id = m.user_id
+            WHERE m.group_id = ?
+            ORDER BY u.username
+            "#
+        )
+            .bind(group_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(members)
+    }
+
+    pub async fn add_group_member(&self, group_id: &str, user_id: &str) -> Result<()> {
+        sqlx::query("INSERT OR IGNORE INTO user_group_members (group_id, user_id, added_at) VALUES (?, ?, ?)")
+            .bind(group_id)
+            .bind(user_id)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn remove_group_member(&self, group_id: &str, user_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM user_group_members WHERE group_id = ? AND user_id = ?")
+            .bind(group_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_access_rules(&self, filter: &AccessRuleQuery) -> Result<Vec<AccessRule>> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM game_access_rules WHERE 1 = 1");
+        for (column, value) in [
+            ("user_id", &filter.user_id),
+            ("group_id", &filter.group_id),
+            ("game_id", &filter.game_id),
+        ] {
+            if let Some(value) = value {
+                query.push(format!(" AND {} = ", column)).push_bind(value.clone());
+            }
+        }
+        query.push(" ORDER BY created_at DESC");
+
+        let rules = query
+            .build_query_as::<AccessRule>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rules)
+    }
+
+    // The request is expected to have been validated by the caller
+    pub async fn create_access_rule(&self, request: &CreateAccessRuleRequest, created_by: &str) -> Result<AccessRule> {
+        let rule = sqlx::query_as::<_, AccessRule>(
+            r#"
+            INSERT INTO game_access_rules (id, user_id, group_id, game_id, library_root, effect, created_by, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#
+        )
+            .bind(Uuid::new_v4().to_string())
+            .bind(&request.user_id)
+            .bind(&request.group_id)
+            .bind(&request.game_id)
+            .bind(&request.library_root)
+            .bind(request.effect.as_str())
+            .bind(created_by)
+            .bind(Utc::now())
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(rule)
+    }
+
+    pub async fn delete_access_rule(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM game_access_rules WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum GameScope<'a> {
+    All,
+    Available(Viewer<'a>),
+    Library(Viewer<'a>),
+}
+
+// The user a store or library query is for. Access rules do not apply to unrestricted
+// viewers (superusers); for everyone else games no rule matches get default_access.
+#[derive(Clone, Copy)]
+pub struct Viewer<'a> {
+    pub user_id: &'a str,
+    pub unrestricted: bool,
+    pub default_access: AccessEffect,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Facet {
+    Genre,
+    Platform,
+    Developer,
+    Publisher,
+    ReleaseYear,
+}
+
+// genres/platforms are JSON arrays of {id, name}; plain strings are accepted too
+fn json_item_name(table: &str) -> String {
+    format!("CASE WHEN {table}.type = 'object' THEN json_extract({table}.value, '$.name') ELSE {table}.value END")
+}
+
+// FROM/WHERE shared by the game list, its count query and the facet queries
+fn push_game_source(query: &mut QueryBuilder<'_, Sqlite>, filter: &GameFilter, scope: GameScope<'_>, skip: Option<Facet>) {
+    match filter.q.as_deref().and_then(fts_match_expression) {
+        Some(fts_query) => {
+            query.push(" FROM games_fts JOIN games ON games.id = games_fts.game_id WHERE games_fts MATCH ");
+            query.push_bind(fts_query);
+        }
+        None => {
+            query.push(" FROM games WHERE 1 = 1");
+        }
+    }
+
+    match scope {
+        GameScope::All => {}
+        GameScope::Available(viewer) => {
+            query.push(" AND games.is_available = ").push_bind(true);
+            push_visibility(query, viewer);
+        }
+        GameScope::Library(viewer) => {
+            query
+                .push(" AND games.id IN (SELECT game_id FROM user_games WHERE user_id = ")
+                .push_bind(viewer.user_id.to_string())
+                .push(")");
+            push_visibility(query, viewer);
+        }
+    }
+
+    for (facet, column, value) in [
+        (Facet::Genre, "genres", &filter.genre),
+        (Facet::Platform, "platforms", &filter.platform),
+    ] {
+        if let Some(value) = value.as_ref().filter(|_| skip != Some(facet)) {
+            query
+                .push(format!(
+                    " AND EXISTS (SELECT 1 FROM json_each(CASE WHEN json_valid(games.{column}) THEN games.{column} ELSE '[]' END) \
+                     WHERE {} = ",
+                    json_item_name("json_each")
+                ))
+                .push_bind(value.clone())
+                .push(" COLLATE NOCASE)");
+        }
+    }
+
+    for (facet, column, value) in [
+        (Facet::Developer, "games.developer", &filter.developer),
+        (Facet::Publisher, "games.publisher", &filter.publisher),
+    ] {
+        if let Some(value) = value.as_ref().filter(|_| skip != Some(facet)) {
+            query
+                .push(format!(" AND {} = ", column))
+                .push_bind(value.clone())
+                .push(" COLLATE NOCASE");
+        }
+    }
+
+    if skip != Some(Facet::ReleaseYear) {
+        if let Some(year_from) = filter.year_from {
+            query.push(" AND CAST(substr(games.release_date, 1, 4) AS INTEGER) >= ").push_bind(year_from);
+        }
+        if let Some(year_to) = filter.year_to {
+            query.push(" AND CAST(substr(games.release_date, 1, 4) AS INTEGER) <= ").push_bind(year_to);
+        }
+    }
+
+    if let Some(min_rating) = filter.min_rating {
+        query.push(" AND games.rating >= ").push_bind(min_rating);
+    }
+}
+
+// Only the most specific rule matching the viewer and the game counts: rules for the user
+// beat rules for their groups, rules for the game beat rules for its library root, and deny
+// beats allow between otherwise equal rules
+fn push_visibility(query: &mut QueryBuilder<'_, Sqlite>, viewer: Viewer<'_>) {
+    if viewer.unrestricted {
+        return;
+    }
+
+    query
+        .push(
+            " AND COALESCE((SELECT r.effect FROM game_access_rules r \
+             WHERE (r.user_id = ",
+        )
+        .push_bind(viewer.user_id.to_string())
+        .push(" OR r.group_id IN (SELECT group_id FROM user_group_members WHERE user_id = ")
+        .push_bind(viewer.user_id.to_string())
+        .push(
+            ")) \
+             AND (r.game_id = games.id OR games.file_path = r.library_root \
+                  OR substr(games.file_path, 1, length(r.library_root) + 1) = r.library_root || '/') \
+             ORDER BY r.user_id IS NOT NULL DESC, r.game_id IS NOT NULL DESC, r.effect = 'deny' DESC \
+             LIMIT 1), ",
+        )
+        .push_bind(viewer.default_access.as_str())
+        .push(") = 'allow'");
+}
+
+fn parse_overrides(raw: Option<&str>) -> Vec<String> {
+    raw.and_then(|raw| serde_json::from_str(raw).ok()).unwrap_or_default()
+}
+
+// Same shape the metadata provider writes for genres/platforms: [{"name": ...}]
+fn named_list_json(names: &[String]) -> String {
+    let items: Vec<serde_json::Value> = names
+        .iter()
+        .map(|name| serde_json::json!({ "name": name }))
+        .collect();
+    serde_json::Value::Array(items).to_string()
+}
+
+// Turn free-form user input into a safe FTS5 expression: every word quoted and prefix-matched
+fn fts_match_expression(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .map(|term| term.replace('"', ""))
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("\"{}\"*", term))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+// New struct for user game details
+#[derive(Debug, sqlx::FromRow)]
+pub struct UserGameWithDetails {
+    pub user_game_id: String,
+    pub is_installed: bool,
+    pub install_path: Option<String>,
+    pub installed_at: Option<DateTime<Utc>>,
+    pub last_played: Option<DateTime<Utc>>,
+    pub play_time_minutes: i64,
+    pub installed_build_id: Option<String>,
+
+    // Game details (flattened from games table)
+    pub id: String,
+    #[allow(dead_code)]
+    pub igdb_id: Option<i32>,
+    pub name: String,
+    pub summary: Option<String>,
+    #[allow(dead_code)]
+    pub storyline: Option<String>,
+    #[allow(dead_code)]
+    pub rating: Option<f64>,
+    #[allow(dead_code)]
+    pub release_date: Option<DateTime<Utc>>,
+    #[allow(dead_code)]
+    pub cover_url: Option<String>,
+    #[allow(dead_code)]
+    pub screenshots: Option<String>,
+    #[allow(dead_code)]
+    pub genres: Option<String>,
+    #[allow(dead_code)]
+    pub platforms: Option<String>,
+    pub developer: Option<String>,
+    pub publisher: Option<String>,
+    #[allow(dead_code)]
+    pub file_path: Option<String>,
+    #[allow(dead_code)]
+    pub file_size: Option<i64>,
+    #[allow(dead_code)]
+    pub is_available: bool,
+    #[allow(dead_code)]
+    pub added_by: Option<String>,
+    #[allow(dead_code)]
+    pub created_at: DateTime<Utc>,
+    #[allow(dead_code)]
+    pub updated_at: DateTime<Utc>,
+    pub current_build_id: Option<String>,
+}
+
+#[cfg(test)]
+mod job_tests {
+    use super::*;
+
+    const SCAN: &str = r#"{"kind":"library_scan"}"#;
+    const REFRESH: &str = r#"{"kind":"metadata_refresh","game_id":"g1"}"#;
+
+    async fn claim(db: &Database) -> Option<String> {
+        db.claim_next_job().await.unwrap().map(|job| job.id)
+    }
+
+    #[tokio::test]
+    async fn duplicate_of_a_queued_job_returns_the_queued_one() {
+        let db = Database::in_memory().await.unwrap();
+
+        let first = db.create_job("library_scan", SCAN, 3, None).await.unwrap();
+        let second = db.create_job("library_scan", SCAN, 3, Some("admin")).await.unwrap();
+        let other = db.create_job("metadata_refresh", REFRESH, 3, None).await.unwrap();
+
+        assert_eq!(second.id, first.id);
+        assert_ne!(other.id, first.id);
+        assert_eq!(db.get_jobs(Some("queued"), None, 10).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn duplicate_of_a_running_job_waits_for_it() {
+        let db = Database::in_memory().await.unwrap();
+        let running = db.create_job("library_scan", SCAN, 3, None).await.unwrap();
+        assert_eq!(claim(&db).await, Some(running.id.clone()));
+
+        // Work queued mid-run gets its own job, but it does not start alongside the first
+        let queued = db.create_job("library_scan", SCAN, 3, None).await.unwrap();
+        assert_ne!(queued.id, running.id);
+        assert_eq!(claim(&db).await, None);
+
+        // Other work is not held up behind it
+        let other = db.create_job("metadata_refresh", REFRESH, 3, None).await.unwrap();
+        assert_eq!(claim(&db).await, Some(other.id));
+
+        db.finish_job(&running.id, "completed", None, None).await.unwrap();
+        assert_eq!(claim(&db).await, Some(queued.id));
+    }
+
+    #[tokio::test]
+    async fn jobs_waiting_for_a_retry_are_not_claimed() {
+        let db = Database::in_memory().await.unwrap();
+        let job = db.create_job("library_scan", SCAN, 3, None).await.unwrap();
+        assert_eq!(claim(&db).await, Some(job.id.clone()));
+
+        db.retry_job(&job.id, "timed out", std::time::Duration::from_secs(60)).await.unwrap();
+        assert_eq!(claim(&db).await, None);
+
+        // Asking for the same work again merges into the retry rather than running it twice
+        let again = db.create_job("library_scan", SCAN, 3, None).await.unwrap();
+        assert_eq!(again.id, job.id);
+    }
 }
@@ -0,0 +1,268 @@
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use std::{io::SeekFrom, path::Path};
+use tokio::{fs::File, io::{AsyncReadExt, AsyncSeekExt}};
+use tokio_util::io::ReaderStream;
+
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    Full,
+    Partial { start: u64, end: u64 },
+    Unsatisfiable,
+}
+
+// Stream a file from disk, honouring single-part Range/If-Range requests so clients can resume
+pub async fn serve_file(path: &Path, headers: &HeaderMap) -> Result<Response, StatusCode> {
+    let metadata = match tokio::fs::metadata(path).await {
+        Ok(metadata) if metadata.is_file() => metadata,
+        Ok(_) => {
+            tracing::warn!("Download path is not a file: {}", path.display());
+            return Err(StatusCode::NOT_FOUND);
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            tracing::warn!("Download file missing on disk: {}", path.display());
+            return Err(StatusCode::NOT_FOUND);
+        }
+        Err(e) => {
+            tracing::error!("Failed to read file metadata for {}: {}", path.display(), e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let size = metadata.len();
+    let modified: DateTime<Utc> = metadata
+        .modified()
+        .map(DateTime::from)
+        .unwrap_or_else(|_| Utc::now());
+    let etag = format!("\"{:x}-{:x}\"", size, modified.timestamp());
+    let last_modified = modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+
+    // If-Range only lets the Range through when the client's copy is still current
+    let range_allowed = match headers.get(header::IF_RANGE).and_then(|v| v.to_str().ok()) {
+        Some(validator) => validator == etag || validator == last_modified,
+        None => true,
+    };
+
+    let range = match headers.get(header::RANGE).and_then(|v| v.to_str().ok()) {
+        Some(value) if range_allowed => parse_range(value, size),
+        _ => ByteRange::Full,
+    };
+
+    let mut builder = Response::builder()
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, &etag)
+        .header(header::LAST_MODIFIED, &last_modified);
+
+    let (start, length) = match range {
+        ByteRange::Full => {
+            builder = builder.status(StatusCode::OK);
+            (0, size)
+        }
+        ByteRange::Partial { start, end } => {
+            builder = builder
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, size));
+            (start, end - start + 1)
+        }
+        ByteRange::Unsatisfiable => {
+            return builder
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+                .body(Body::empty())
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let mut file = File::open(path).await.map_err(|e| {
+        tracing::error!("Failed to open {}: {}", path.display(), e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if start > 0 {
+        file.seek(SeekFrom::Start(start)).await.map_err(|e| {
+            tracing::error!("Failed to seek in {}: {}", path.display(), e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    }
+
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().replace('"', ""))
+        .unwrap_or_else(|| "download".to_string());
+    let disposition = HeaderValue::from_str(&format!("attachment; filename=\"{}\"", file_name))
+        .unwrap_or_else(|_| HeaderValue::from_static("attachment"));
+
+    builder
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::CONTENT_LENGTH, length)
+        .header(header::CONTENT_DISPOSITION, disposition)
+        .body(Body::from_stream(ReaderStream::new(file.take(length))))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+// Multi-range and malformed headers fall back to the full file, which RFC 9110 permits
+fn parse_range(value: &str, size: u64) -> ByteRange {
+    let spec = match value.trim().strip_prefix("bytes=") {
+        Some(spec) if !spec.contains(',') => spec.trim(),
+        _ => return ByteRange::Full,
+    };
+
+    let (start, end) = match spec.split_once('-') {
+        Some(parts) => parts,
+        None => return ByteRange::Full,
+    };
+
+    match (start.trim(), end.trim()) {
+        ("", "") => ByteRange::Full,
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(_) if size == 0 => ByteRange::Unsatisfiable,
+            Ok(suffix) => ByteRange::Partial {
+                start: size.saturating_sub(suffix),
+                end: size - 1,
+            },
+            Err(_) => ByteRange::Full,
+        },
+        (start, end) => {
+            let start = match start.parse::<u64>() {
+                Ok(start) => start,
+                Err(_) => return ByteRange::Full,
+            };
+            let end = if end.is_empty() {
+                size.saturating_sub(1)
+            } else {
+                match end.parse::<u64>() {
+                    Ok(end) if end >= start => end.min(size.saturating_sub(1)),
+                    _ => return ByteRange::Full,
+                }
+            };
+
+            if start >= size {
+                ByteRange::Unsatisfiable
+            } else {
+                ByteRange::Partial { start, end }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_single_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), ByteRange::Partial { start: 0, end: 99 });
+        assert_eq!(parse_range("bytes=-500", 1000), ByteRange::Partial { start: 500, end: 999 });
+        assert_eq!(parse_range("bytes=-5000", 1000), ByteRange::Partial { start: 0, end: 999 });
+        assert_eq!(parse_range("bytes=100-", 1000), ByteRange::Partial { start: 100, end: 999 });
+        // An end past the file is cut to its last byte
+        assert_eq!(parse_range("bytes=900-5000", 1000), ByteRange::Partial { start: 900, end: 999 });
+    }
+
+    #[test]
+    fn refuses_ranges_outside_the_file() {
+        assert_eq!(parse_range("bytes=1000-", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=2000-3000", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-0", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-10", 0), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn falls_back_to_the_full_file() {
+        assert_eq!(parse_range("bytes=500-100", 1000), ByteRange::Full);
+        assert_eq!(parse_range("bytes=0-99,200-299", 1000), ByteRange::Full);
+        assert_eq!(parse_range("bytes=-", 1000), ByteRange::Full);
+        assert_eq!(parse_range("bytes=abc-", 1000), ByteRange::Full);
+        assert_eq!(parse_range("items=0-99", 1000), ByteRange::Full);
+    }
+
+    // A 1000-byte file of 0, 1, ..., 255, 0, 1, ... that is removed when dropped
+    struct TestFile(std::path::PathBuf);
+
+    impl TestFile {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("download-test-{}", uuid::Uuid::new_v4()));
+            std::fs::write(&path, (0..1000u32).map(|i| i as u8).collect::<Vec<_>>()).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TestFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    async fn get(file: &TestFile, headers: &[(header::HeaderName, &str)]) -> (StatusCode, HeaderMap, Vec<u8>) {
+        let mut request_headers = HeaderMap::new();
+        for (name, value) in headers {
+            request_headers.insert(name.clone(), HeaderValue::from_str(value).unwrap());
+        }
+        let response = serve_file(&file.0, &request_headers).await.unwrap();
+        let (parts, body) = response.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX).await.unwrap().to_vec();
+        (parts.status, parts.headers, body)
+    }
+
+    #[tokio::test]
+    async fn serves_partial_content() {
+        let file = TestFile::new();
+
+        let (status, headers, body) = get(&file, &[(header::RANGE, "bytes=-500")]).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(headers[header::CONTENT_RANGE], "bytes 500-999/1000");
+        assert_eq!(body.len(), 500);
+        assert_eq!(body[0], (500 % 256) as u8);
+
+        let (status, headers, body) = get(&file, &[(header::RANGE, "bytes=100-")]).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(headers[header::CONTENT_RANGE], "bytes 100-999/1000");
+        assert_eq!(body.len(), 900);
+        assert_eq!(body[0], 100);
+    }
+
+    #[tokio::test]
+    async fn answers_416_past_the_end() {
+        let file = TestFile::new();
+
+        let (status, headers, body) = get(&file, &[(header::RANGE, "bytes=1000-")]).await;
+        assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(headers[header::CONTENT_RANGE], "bytes */1000");
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn sends_the_whole_file_for_ranges_it_does_not_serve() {
+        let file = TestFile::new();
+
+        for range in ["bytes=500-100", "bytes=0-99,200-299"] {
+            let (status, headers, body) = get(&file, &[(header::RANGE, range)]).await;
+            assert_eq!(status, StatusCode::OK, "{}", range);
+            assert!(headers.get(header::CONTENT_RANGE).is_none());
+            assert_eq!(body.len(), 1000);
+        }
+    }
+
+    #[tokio::test]
+    async fn if_range_must_match_the_current_file() {
+        let file = TestFile::new();
+        let (_, headers, _) = get(&file, &[]).await;
+        let etag = headers[header::ETAG].to_str().unwrap().to_string();
+        let last_modified = headers[header::LAST_MODIFIED].to_str().unwrap().to_string();
+
+        for validator in [etag.as_str(), last_modified.as_str()] {
+            let (status, _, body) = get(&file, &[(header::RANGE, "bytes=100-"), (header::IF_RANGE, validator)]).await;
+            assert_eq!(status, StatusCode::PARTIAL_CONTENT, "{}", validator);
+            assert_eq!(body.len(), 900);
+        }
+
+        let (status, _, body) = get(&file, &[(header::RANGE, "bytes=100-"), (header::IF_RANGE, "\"stale\"")]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.len(), 1000);
+    }
+}
@@ -1,29 +1,110 @@
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::Json,
+    body::Bytes,
+    extract::{Extension, Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use crate::{
-    database::Database,
-    igdb_client::IgdbClient,
+    auth::User,
+    database::{Database, GameScope},
+    igdb_client::{IgdbClient, IgdbError},
+    metadata_provider::{MetadataProviders, ProviderInfo},
     auth_service::AuthService,
-    models::{CreateGameRequest, GameListResponse, Game}, // Removed UpdateGameRequest
+    catalog::{self, CatalogFormat},
+    library_scanner::{self, LibraryScanner},
+    jobs::{self, JobPayload, JobQueue},
+    scheduler::Scheduler,
+    manifest,
+    matcher::{self, MatchOutcome},
+    media::{self, MediaMirror},
+    permissions::{DeleteGames, EditGames, EditMetadata, ManageCatalog, RequirePermission, UploadGames},
+    models::{
+        CreateGameRequest, UpdateGameRequest, SetAvailabilityRequest, DeleteGameResponse,
+        GameListResponse, Game, GameFilter, GameBuild, CreateBuildRequest, GameMetadata,
+        PurgeCacheResponse, MetadataMatch, MetadataMatchQuery, QueuedJobsResponse, Job, AccessEffect,
+    },
 };
 
 pub type AppState = std::sync::Arc<AppStateInner>;
 
 pub struct AppStateInner {
     pub db: Database,
-    pub igdb_client: IgdbClient,
+    pub igdb_client: std::sync::Arc<IgdbClient>,
+    pub metadata_providers: MetadataProviders,
     pub auth_service: AuthService,
+    pub library_scanner: LibraryScanner,
+    pub media: MediaMirror,
+    pub jobs: JobQueue,
+    pub scheduler: Scheduler,
+    // What users see of games no access rule covers
+    pub default_game_access: AccessEffect,
+    // Reverse proxies whose X-Forwarded-For is believed when working out client addresses
+    pub trusted_proxies: Vec<std::net::IpAddr>,
 }
 
+// The client's address for login throttling and session lists. Behind a trusted proxy it is
+// the right-most X-Forwarded-For entry that is not itself a trusted proxy.
+pub fn client_ip(state: &AppState, peer: std::net::IpAddr, headers: &axum::http::HeaderMap) -> std::net::IpAddr {
+    if !state.trusted_proxies.contains(&peer) {
+        return peer;
+    }
+
+    let mut client = peer;
+    let forwarded = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect::<Vec<_>>();
+    for entry in forwarded.into_iter().rev() {
+        match entry.trim().parse() {
+            Ok(ip) => client = ip,
+            // A malformed hop could have been written by anyone; stop at the last good one
+            Err(_) => break,
+        }
+        if !state.trusted_proxies.contains(&client) {
+            break;
+        }
+    }
+    client
+}
+
+// Pagination plus catalog search, shared by the store and admin game lists
 #[derive(Deserialize)]
-pub struct PaginationQuery {
+pub struct GameListQuery {
     pub page: Option<i64>,
     pub per_page: Option<i64>,
+    pub q: Option<String>,
+    pub genre: Option<String>,
+    pub platform: Option<String>,
+    pub developer: Option<String>,
+    pub publisher: Option<String>,
+    pub year_from: Option<i32>,
+    pub year_to: Option<i32>,
+    pub min_rating: Option<f64>,
+}
+
+impl GameListQuery {
+    pub fn filter(&self) -> GameFilter {
+        GameFilter {
+            q: self.q.clone(),
+            genre: self.genre.clone(),
+            platform: self.platform.clone(),
+            developer: self.developer.clone(),
+            publisher: self.publisher.clone(),
+            year_from: self.year_from,
+            year_to: self.year_to,
+            min_rating: self.min_rating,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CatalogQuery {
+    pub format: Option<CatalogFormat>,
+    pub dry_run: Option<bool>,
 }
 
 #[derive(Deserialize)]
@@ -32,6 +113,18 @@ pub struct SearchQuery {
     pub limit: Option<u32>,
 }
 
+#[derive(Deserialize)]
+pub struct PurgeCacheQuery {
+    pub expired_only: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct MetadataSearchQuery {
+    pub q: String,
+    pub provider: Option<String>,
+    pub limit: Option<u32>,
+}
+
 #[derive(Serialize)]
 pub struct ApiResponse<T> {
     pub success: bool,
@@ -48,7 +141,6 @@ impl<T> ApiResponse<T> {
         }
     }
 
-    #[allow(dead_code)]
     pub fn error(message: String) -> Self {
         Self {
             success: false,
@@ -67,10 +159,19 @@ pub async fn health_check() -> Json<HashMap<String, String>> {
 
 pub async fn create_game(
     State(state): State<AppState>,
+    _: RequirePermission<UploadGames>,
     Json(request): Json<CreateGameRequest>,
 ) -> Result<Json<ApiResponse<Game>>, StatusCode> {
     match state.db.create_game(request).await {
-        Ok(game) => Ok(Json(ApiResponse::success(game))),
+        Ok(game) => {
+            if game.file_path.is_some() {
+                manifest::queue_generation(&state, game.id.clone()).await;
+            }
+            if game.igdb_id.is_none() {
+                matcher::queue_match(&state, game.id.clone()).await;
+            }
+            Ok(Json(ApiResponse::success(game)))
+        }
         Err(e) => {
             tracing::error!("Failed to create game: {}", e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
@@ -80,23 +181,34 @@ pub async fn create_game(
 
 pub async fn get_games(
     State(state): State<AppState>,
-    Query(params): Query<PaginationQuery>,
+    Query(params): Query<GameListQuery>,
 ) -> Result<Json<ApiResponse<GameListResponse>>, StatusCode> {
     let page = params.page.unwrap_or(1);
     let per_page = params.per_page.unwrap_or(20);
 
-    match state.db.get_games(page, per_page).await {
-        Ok((games, total)) => {
+    let filter = params.filter();
+
+    let (games, total) = match state.db.get_games(&filter, page, per_page).await {
+        Ok(result) => result,
+        Err(e) => {
+            tracing::error!("Failed to get games: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    match state.db.get_game_facets(&filter, GameScope::All).await {
+        Ok(facets) => {
             let response = GameListResponse {
                 games,
                 total,
                 page,
                 per_page,
+                facets,
             };
             Ok(Json(ApiResponse::success(response)))
         }
         Err(e) => {
-            tracing::error!("Failed to get games: {}", e);
+            tracing::error!("Failed to get game facets: {}", e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
@@ -116,8 +228,89 @@ pub async fn get_game(
     }
 }
 
+pub async fn update_game(
+    State(state): State<AppState>,
+    _: RequirePermission<EditGames>,
+    Path(id): Path<String>,
+    Json(request): Json<UpdateGameRequest>,
+) -> Result<Json<ApiResponse<Game>>, StatusCode> {
+    if request.name.as_deref().is_some_and(|name| name.trim().is_empty()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    if let Some(Some(provider)) = &request.metadata_provider {
+        if state.metadata_providers.get(provider).is_none() {
+            tracing::warn!("Unknown metadata provider: {}", provider);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+
+    let file_changed = request.file_path.is_some();
+    let artwork_changed = request.cover_url.is_some() || request.screenshots.is_some();
+
+    match state.db.update_game(&id, request).await {
+        Ok(Some(game)) => {
+            if file_changed && game.file_path.is_some() {
+                manifest::queue_generation(&state, game.id.clone()).await;
+            }
+            if artwork_changed {
+                media::queue_mirror(&state, game.id.clone()).await;
+            }
+            Ok(Json(ApiResponse::success(game)))
+        }
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to update game: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn set_game_availability(
+    State(state): State<AppState>,
+    _: RequirePermission<EditGames>,
+    Path(id): Path<String>,
+    Json(request): Json<SetAvailabilityRequest>,
+) -> Result<Json<ApiResponse<Game>>, StatusCode> {
+    let update = UpdateGameRequest {
+        is_available: Some(request.is_available),
+        ..Default::default()
+    };
+
+    match state.db.update_game(&id, update).await {
+        Ok(Some(game)) => Ok(Json(ApiResponse::success(game))),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to set game availability: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn delete_game(
+    State(state): State<AppState>,
+    _: RequirePermission<DeleteGames>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<DeleteGameResponse>>, StatusCode> {
+    match state.db.delete_game(&id).await {
+        Ok(Some(affected_libraries)) => {
+            tracing::info!("Deleted game {} (referenced by {} libraries)", id, affected_libraries);
+            if let Err(e) = state.media.remove_game(&id).await {
+                tracing::warn!("Failed to remove media for deleted game {}: {}", id, e);
+            }
+            Ok(Json(ApiResponse::success(DeleteGameResponse { id, affected_libraries })))
+        }
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to delete game: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
 pub async fn search_igdb_games(
     State(state): State<AppState>,
+    _: RequirePermission<EditMetadata>,
     Query(params): Query<SearchQuery>,
 ) -> Result<Json<ApiResponse<Vec<crate::models::IgdbGame>>>, StatusCode> {
     let limit = params.limit.unwrap_or(10);
@@ -126,15 +319,315 @@ pub async fn search_igdb_games(
         Ok(games) => Ok(Json(ApiResponse::success(games))),
         Err(e) => {
             tracing::error!("Failed to search IGDB: {}", e);
+            Err(metadata_error_status(&e))
+        }
+    }
+}
+
+pub async fn purge_igdb_cache(
+    State(state): State<AppState>,
+    _: RequirePermission<EditMetadata>,
+    Query(params): Query<PurgeCacheQuery>,
+) -> Result<Json<ApiResponse<PurgeCacheResponse>>, StatusCode> {
+    match state.igdb_client.purge_cache(params.expired_only.unwrap_or(false)).await {
+        Ok(purged) => {
+            tracing::info!("Purged {} IGDB cache entries", purged);
+            Ok(Json(ApiResponse::success(PurgeCacheResponse { purged })))
+        }
+        Err(e) => {
+            tracing::error!("Failed to purge IGDB cache: {}", e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
 }
 
+pub async fn search_metadata(
+    State(state): State<AppState>,
+    _: RequirePermission<EditMetadata>,
+    Query(params): Query<MetadataSearchQuery>,
+) -> Result<Json<ApiResponse<Vec<GameMetadata>>>, StatusCode> {
+    let provider = match params.provider.as_deref() {
+        Some(id) => state.metadata_providers.get(id).ok_or(StatusCode::BAD_REQUEST)?,
+        None => state.metadata_providers.default_provider(),
+    };
+    let limit = params.limit.unwrap_or(10);
+
+    match provider.search(&params.q, limit).await {
+        Ok(games) => Ok(Json(ApiResponse::success(games))),
+        Err(e) => {
+            tracing::error!("Failed to search {}: {}", provider.display_name(), e);
+            Err(metadata_error_status(&e))
+        }
+    }
+}
+
+pub async fn list_metadata_providers(
+    State(state): State<AppState>,
+) -> Json<ApiResponse<Vec<ProviderInfo>>> {
+    Json(ApiResponse::success(state.metadata_providers.list()))
+}
+
 pub async fn fetch_game_metadata(
     State(state): State<AppState>,
+    _: RequirePermission<EditMetadata>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<Game>>, StatusCode> {
+    match state.db.get_game_by_id(&id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get game: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    match matcher::refresh_game(&state, &id).await {
+        Ok(MatchOutcome::NotFound) => return Err(StatusCode::NOT_FOUND),
+        Ok(_) => {}
+        Err(e) => {
+            tracing::error!("Failed to fetch game metadata: {}", e);
+            return Err(metadata_error_status(&e));
+        }
+    }
+
+    match state.db.get_game_by_id(&id).await {
+        Ok(Some(updated_game)) => Ok(Json(ApiResponse::success(updated_game))),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get updated game: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn run_metadata_matching(
+    State(state): State<AppState>,
+    _: RequirePermission<EditMetadata>,
+) -> Result<(StatusCode, Json<ApiResponse<QueuedJobsResponse>>), StatusCode> {
+    match state.db.get_unlinked_game_ids().await {
+        Ok(game_ids) => {
+            let queued = game_ids.len();
+            for game_id in game_ids {
+                matcher::queue_match(&state, game_id).await;
+            }
+            Ok((StatusCode::ACCEPTED, Json(ApiResponse::success(QueuedJobsResponse { queued }))))
+        }
+        Err(e) => {
+            tracing::error!("Failed to list unlinked games: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// One refresh job per game; unlinked games go through the matcher instead
+pub async fn refresh_all_metadata(
+    State(state): State<AppState>,
+    _: RequirePermission<EditMetadata>,
+    Extension(admin): Extension<User>,
+) -> Result<(StatusCode, Json<ApiResponse<QueuedJobsResponse>>), StatusCode> {
+    let games = match state.db.get_all_games().await {
+        Ok(games) => games,
+        Err(e) => {
+            tracing::error!("Failed to list games: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let mut queued = 0;
+    for game in games {
+        let payload = JobPayload::RefreshMetadata { game_id: game.id };
+        match jobs::enqueue(&state, payload, Some(&admin.id)).await {
+            Ok(_) => queued += 1,
+            Err(e) => {
+                tracing::error!("Failed to queue metadata refresh: {}", e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+    }
+
+    Ok((StatusCode::ACCEPTED, Json(ApiResponse::success(QueuedJobsResponse { queued }))))
+}
+
+pub async fn list_metadata_matches(
+    State(state): State<AppState>,
+    _: RequirePermission<EditMetadata>,
+    Query(params): Query<MetadataMatchQuery>,
+) -> Result<Json<ApiResponse<Vec<MetadataMatch>>>, StatusCode> {
+    // The review queue by default; ?status=all for the full history
+    let status = match params.status.as_deref() {
+        None => Some("pending"),
+        Some("all") => None,
+        Some(status) => Some(status),
+    };
+
+    match state.db.get_metadata_matches(status, params.game_id.as_deref()).await {
+        Ok(matches) => Ok(Json(ApiResponse::success(matches))),
+        Err(e) => {
+            tracing::error!("Failed to list metadata matches: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn accept_metadata_match(
+    State(state): State<AppState>,
+    _: RequirePermission<EditMetadata>,
+    Extension(admin): Extension<User>,
     Path(id): Path<String>,
 ) -> Result<Json<ApiResponse<Game>>, StatusCode> {
+    let candidate = match state.db.get_metadata_match(&id).await {
+        Ok(Some(candidate)) => candidate,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get metadata match: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let metadata: GameMetadata = serde_json::from_str(&candidate.metadata).map_err(|e| {
+        tracing::error!("Stored metadata for match {} is unreadable: {}", id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    match state.db.resolve_metadata_match(&id, true, &admin.id).await {
+        Ok(true) => {}
+        Ok(false) => return Err(StatusCode::CONFLICT),
+        Err(e) => {
+            tracing::error!("Failed to accept metadata match: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    if let Err(e) = state.db.update_game_metadata(&candidate.game_id, &metadata).await {
+        tracing::error!("Failed to update game metadata: {}", e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    media::queue_mirror(&state, candidate.game_id.clone()).await;
+
+    match state.db.get_game_by_id(&candidate.game_id).await {
+        Ok(Some(game)) => Ok(Json(ApiResponse::success(game))),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get updated game: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn reject_metadata_match(
+    State(state): State<AppState>,
+    _: RequirePermission<EditMetadata>,
+    Extension(admin): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<MetadataMatch>>, StatusCode> {
+    match state.db.resolve_metadata_match(&id, false, &admin.id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return match state.db.get_metadata_match(&id).await {
+                Ok(Some(_)) => Err(StatusCode::CONFLICT),
+                Ok(None) => Err(StatusCode::NOT_FOUND),
+                Err(e) => {
+                    tracing::error!("Failed to get metadata match: {}", e);
+                    Err(StatusCode::INTERNAL_SERVER_ERROR)
+                }
+            };
+        }
+        Err(e) => {
+            tracing::error!("Failed to reject metadata match: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    match state.db.get_metadata_match(&id).await {
+        Ok(Some(rejected)) => Ok(Json(ApiResponse::success(rejected))),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get metadata match: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn scan_library(
+    State(state): State<AppState>,
+    _: RequirePermission<UploadGames>,
+    Extension(admin): Extension<User>,
+) -> Result<(StatusCode, Json<ApiResponse<Job>>), StatusCode> {
+    if state.library_scanner.roots().is_empty() {
+        tracing::warn!("Library scan requested but LIBRARY_ROOTS is not configured");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // The scan report ends up in the job's result
+    match jobs::enqueue(&state, JobPayload::ScanLibrary, Some(&admin.id)).await {
+        Ok(job) => Ok((StatusCode::ACCEPTED, Json(ApiResponse::success(job)))),
+        Err(e) => {
+            tracing::error!("Failed to queue library scan: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn regenerate_manifest(
+    State(state): State<AppState>,
+    _: RequirePermission<EditGames>,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<ApiResponse<Job>>), StatusCode> {
+    match state.db.get_game_by_id(&id).await {
+        Ok(Some(game)) if game.file_path.is_some() => match manifest::queue_generation(&state, id).await {
+            Some(job) => Ok((StatusCode::ACCEPTED, Json(ApiResponse::success(job)))),
+            None => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        },
+        Ok(Some(_)) => Err(StatusCode::BAD_REQUEST),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get game: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn mirror_game_media(
+    State(state): State<AppState>,
+    _: RequirePermission<EditGames>,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<ApiResponse<Job>>), StatusCode> {
+    if !state.media.enabled() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    match state.db.get_game_by_id(&id).await {
+        Ok(Some(_)) => match media::queue_mirror(&state, id).await {
+            Some(job) => Ok((StatusCode::ACCEPTED, Json(ApiResponse::success(job)))),
+            None => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        },
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get game: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn list_game_builds(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<Vec<GameBuild>>>, StatusCode> {
+    match state.db.get_game_builds(&id).await {
+        Ok(builds) => Ok(Json(ApiResponse::success(builds))),
+        Err(e) => {
+            tracing::error!("Failed to get game builds: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn create_game_build(
+    State(state): State<AppState>,
+    _: RequirePermission<UploadGames>,
+    Path(id): Path<String>,
+    Json(request): Json<CreateBuildRequest>,
+) -> Result<Json<ApiResponse<GameBuild>>, StatusCode> {
     let game = match state.db.get_game_by_id(&id).await {
         Ok(Some(game)) => game,
         Ok(None) => return Err(StatusCode::NOT_FOUND),
@@ -144,33 +637,172 @@ pub async fn fetch_game_metadata(
         }
     };
 
-    if let Some(igdb_id) = game.igdb_id {
-        match state.igdb_client.get_game_by_id(igdb_id).await {
-            Ok(Some(igdb_game)) => {
-                if let Err(e) = state.db.update_game_metadata(&id, &igdb_game).await {
-                    tracing::error!("Failed to update game metadata: {}", e);
+    let file_size = match request.file_size {
+        Some(size) => size,
+        None => {
+            let path = std::path::PathBuf::from(&request.file_path);
+            match tokio::task::spawn_blocking(move || library_scanner::path_size(&path)).await {
+                Ok(Ok(size)) => size as i64,
+                Ok(Err(e)) => {
+                    tracing::warn!("Build file path {} is not readable: {}", request.file_path, e);
+                    return Err(StatusCode::BAD_REQUEST);
+                }
+                Err(e) => {
+                    tracing::error!("Failed to size build files: {}", e);
                     return Err(StatusCode::INTERNAL_SERVER_ERROR);
                 }
+            }
+        }
+    };
 
-                match state.db.get_game_by_id(&id).await {
-                    Ok(Some(updated_game)) => Ok(Json(ApiResponse::success(updated_game))),
-                    Ok(None) => Err(StatusCode::NOT_FOUND),
-                    Err(e) => {
-                        tracing::error!("Failed to get updated game: {}", e);
-                        Err(StatusCode::INTERNAL_SERVER_ERROR)
-                    }
+    // The file a game was created with becomes its first build, so the scanner keeps treating it
+    // as part of this game rather than importing it again once file_path moves on
+    if game.current_build_id.is_none() {
+        if let Some(file_path) = game.file_path.filter(|path| *path != request.file_path) {
+            let initial = CreateBuildRequest {
+                version: "initial".to_string(),
+                release_notes: None,
+                file_path,
+                file_size: game.file_size,
+                make_current: Some(false),
+            };
+            if let Err(e) = state.db.create_game_build(&id, &initial, game.file_size).await {
+                if !is_unique_violation(&e) {
+                    tracing::error!("Failed to record initial build: {}", e);
+                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
                 }
             }
-            Ok(None) => {
-                tracing::warn!("Game not found in IGDB: {}", igdb_id);
-                Err(StatusCode::NOT_FOUND)
-            }
-            Err(e) => {
-                tracing::error!("Failed to fetch from IGDB: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+
+    let build = match state.db.create_game_build(&id, &request, Some(file_size)).await {
+        Ok(build) => build,
+        Err(e) if is_unique_violation(&e) => return Err(StatusCode::CONFLICT),
+        Err(e) => {
+            tracing::error!("Failed to create game build: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    match state.db.absorb_duplicate_games(&id, &build.file_path).await {
+        Ok(duplicates) => {
+            for duplicate in duplicates {
+                tracing::info!("Removed game {} imported from build {} of game {}", duplicate, build.file_path, id);
+                if let Err(e) = state.media.remove_game(&duplicate).await {
+                    tracing::warn!("Failed to remove media of game {}: {}", duplicate, e);
+                }
             }
         }
-    } else {
-        Ok(Json(ApiResponse::success(game)))
+        Err(e) => tracing::error!("Failed to remove games duplicating build {}: {}", build.file_path, e),
+    }
+
+    if request.make_current.unwrap_or(true) {
+        if let Err(e) = state.db.set_current_build(&id, &build.id).await {
+            tracing::error!("Failed to set current build: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        manifest::queue_generation(&state, id).await;
+    }
+
+    Ok(Json(ApiResponse::success(build)))
+}
+
+pub async fn set_current_build(
+    State(state): State<AppState>,
+    _: RequirePermission<EditGames>,
+    Path((id, build_id)): Path<(String, String)>,
+) -> Result<Json<ApiResponse<Game>>, StatusCode> {
+    match state.db.set_current_build(&id, &build_id).await {
+        Ok(Some(game)) => {
+            manifest::queue_generation(&state, id).await;
+            Ok(Json(ApiResponse::success(game)))
+        }
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to set current build: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// Upstream metadata failures are not our bugs; tell the client what actually went wrong
+pub fn metadata_error_status(error: &anyhow::Error) -> StatusCode {
+    match error.downcast_ref::<IgdbError>() {
+        Some(IgdbError::NotConfigured) => StatusCode::SERVICE_UNAVAILABLE,
+        Some(IgdbError::RateLimited) => StatusCode::TOO_MANY_REQUESTS,
+        Some(IgdbError::NotFound) => StatusCode::NOT_FOUND,
+        Some(IgdbError::Auth(_) | IgdbError::Upstream(_) | IgdbError::Request(_)) => StatusCode::BAD_GATEWAY,
+        None => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+pub fn is_unique_violation(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<sqlx::Error>()
+        .and_then(|e| e.as_database_error())
+        .is_some_and(|e| e.is_unique_violation())
+}
+
+pub async fn export_catalog(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageCatalog>,
+    Query(params): Query<CatalogQuery>,
+) -> Result<Response, StatusCode> {
+    let format = params.format.unwrap_or(CatalogFormat::Json);
+
+    match catalog::export(&state.db, format).await {
+        Ok(body) => {
+            let disposition = format!(
+                "attachment; filename=\"catalog-{}.{}\"",
+                chrono::Utc::now().format("%Y%m%d-%H%M%S"),
+                format.extension()
+            );
+            Ok((
+                [
+                    (header::CONTENT_TYPE, format.content_type().to_string()),
+                    (header::CONTENT_DISPOSITION, disposition),
+                ],
+                body,
+            )
+                .into_response())
+        }
+        Err(e) => {
+            tracing::error!("Failed to export catalog: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn import_catalog(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageCatalog>,
+    Query(params): Query<CatalogQuery>,
+    body: Bytes,
+) -> Result<Response, StatusCode> {
+    let format = params.format.unwrap_or(CatalogFormat::Json);
+
+    let entries = match catalog::parse(&body, format) {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::warn!("Rejected catalog import: {}", e);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    match catalog::import(&state.db, entries, params.dry_run.unwrap_or(false)).await {
+        Ok(report) if !report.errors.is_empty() => {
+            tracing::warn!("Rejected catalog import with {} errors", report.errors.len());
+            let response = ApiResponse {
+                success: false,
+                error: Some("Catalog has invalid rows; nothing was imported".to_string()),
+                data: Some(report),
+            };
+            Ok((StatusCode::BAD_REQUEST, Json(response)).into_response())
+        }
+        Ok(report) => Ok(Json(ApiResponse::success(report)).into_response()),
+        Err(e) => {
+            tracing::error!("Failed to import catalog: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
     }
 }
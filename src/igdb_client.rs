@@ -1,70 +1,508 @@
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
 use anyhow::{Result, anyhow};
-use crate::models::IgdbGame;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, Semaphore};
+use crate::{
+    database::Database,
+    metadata_provider::{MetadataProvider, ProviderCapabilities},
+    models::{GameMetadata, IgdbGame},
+};
 
+// Refresh this long before the reported expiry so in-flight requests never carry a stale token
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+// IGDB allows 4 requests per second and 8 open requests per client
+const REQUESTS_PER_SECOND: f64 = 4.0;
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+
+// Retries for 429 and 5xx responses, backing off 500ms, 1s, 2s (plus jitter)
+const MAX_RETRIES: u32 = 3;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub enum IgdbError {
+    NotConfigured,
+    Auth(StatusCode),
+    RateLimited,
+    NotFound,
+    Upstream(StatusCode),
+    Request(reqwest::Error),
+}
+
+impl fmt::Display for IgdbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IgdbError::NotConfigured => write!(f, "IGDB is not configured: set IGDB_CLIENT_SECRET or IGDB_ACCESS_TOKEN"),
+            IgdbError::Auth(status) => write!(f, "IGDB authentication failed: {}", status),
+            IgdbError::RateLimited => write!(f, "IGDB rate limit exceeded"),
+            IgdbError::NotFound => write!(f, "IGDB resource not found"),
+            IgdbError::Upstream(status) => write!(f, "IGDB API request failed: {}", status),
+            IgdbError::Request(e) => write!(f, "IGDB request error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for IgdbError {}
+
+impl IgdbError {
+    fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => IgdbError::Auth(status),
+            StatusCode::TOO_MANY_REQUESTS => IgdbError::RateLimited,
+            StatusCode::NOT_FOUND => IgdbError::NotFound,
+            _ => IgdbError::Upstream(status),
+        }
+    }
+}
+
+// Token bucket shared by every clone of the client
+struct RateLimiter {
+    state: std::sync::Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    fn new() -> Self {
+        Self {
+            state: std::sync::Mutex::new((REQUESTS_PER_SECOND, Instant::now())),
+        }
+    }
+
+    async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let (tokens, last) = &mut *state;
+                let now = Instant::now();
+                *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * REQUESTS_PER_SECOND)
+                    .min(REQUESTS_PER_SECOND);
+                *last = now;
+
+                if *tokens >= 1.0 {
+                    *tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - *tokens) / REQUESTS_PER_SECOND)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+struct CachedToken {
+    access_token: String,
+    refresh_at: Instant,
+}
+
+// Responses younger than `ttl` are served as-is; older ones within `stale_ttl` are served
+// immediately while a background request refreshes them.
+#[derive(Clone)]
+struct IgdbCache {
+    db: Database,
+    ttl: Duration,
+    stale_ttl: Duration,
+    refreshing: Arc<std::sync::Mutex<HashSet<String>>>,
+}
+
+#[derive(Clone)]
 pub struct IgdbClient {
     client: Client,
     client_id: String,
-    access_token: String,
+    // With a secret the client runs the Twitch client-credentials flow itself;
+    // without one it falls back to the static IGDB_ACCESS_TOKEN.
+    client_secret: Option<String>,
+    static_token: Option<String>,
+    token_url: String,
+    api_url: String,
+    token: Arc<Mutex<Option<CachedToken>>>,
+    cache: Option<IgdbCache>,
+    rate_limiter: Arc<RateLimiter>,
+    concurrency: Arc<Semaphore>,
 }
 
 impl IgdbClient {
-    pub fn new(client_id: String, access_token: String) -> Self {
+    pub fn new(
+        client_id: String,
+        client_secret: Option<String>,
+        static_token: Option<String>,
+        token_url: String,
+        api_url: String,
+    ) -> Self {
         Self {
             client: Client::new(),
             client_id,
-            access_token,
+            client_secret,
+            static_token,
+            token_url,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            token: Arc::new(Mutex::new(None)),
+            cache: None,
+            rate_limiter: Arc::new(RateLimiter::new()),
+            concurrency: Arc::new(Semaphore::new(MAX_CONCURRENT_REQUESTS)),
         }
     }
 
-    // Extract common request logic to avoid duplication
-    async fn make_igdb_request(&self, body: String) -> Result<Vec<IgdbGame>> {
+    // A zero TTL leaves caching disabled
+    pub fn with_cache(mut self, db: Database, ttl: Duration, stale_ttl: Duration) -> Self {
+        if !ttl.is_zero() {
+            self.cache = Some(IgdbCache {
+                db,
+                ttl,
+                stale_ttl,
+                refreshing: Arc::new(std::sync::Mutex::new(HashSet::new())),
+            });
+        }
+        self
+    }
+
+    // With `expired_only`, keeps entries that could still be served (fresh or stale)
+    pub async fn purge_cache(&self, expired_only: bool) -> Result<u64> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return Ok(0),
+        };
+
+        let older_than = if expired_only {
+            Some(Utc::now() - chrono::Duration::from_std(cache.ttl + cache.stale_ttl)?)
+        } else {
+            None
+        };
+
+        cache.db.purge_igdb_cache(older_than).await
+    }
+
+    async fn access_token(&self) -> Result<String> {
+        let client_secret = match &self.client_secret {
+            Some(secret) => secret,
+            None => return self.static_token.clone().ok_or_else(|| IgdbError::NotConfigured.into()),
+        };
+
+        // Holding the lock while fetching keeps concurrent callers from all requesting a token
+        let mut cached = self.token.lock().await;
+        if let Some(token) = cached.as_ref() {
+            if Instant::now() < token.refresh_at {
+                return Ok(token.access_token.clone());
+            }
+        }
+
         let response = self.client
-            .post("https://api.igdb.com/v4/games")
-            .header("Client-ID", &self.client_id)
-            .header("Authorization", format!("Bearer {}", self.access_token))
-            .header("Content-Type", "application/json")
-            .body(body)
+            .post(&self.token_url)
+            .form(&[
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", client_secret.as_str()),
+                ("grant_type", "client_credentials"),
+            ])
             .send()
-            .await?;
+            .await
+            .map_err(IgdbError::Request)?;
 
         if !response.status().is_success() {
-            return Err(anyhow!("IGDB API request failed: {}", response.status()));
+            tracing::error!("IGDB token request failed: {}", response.status());
+            return Err(match response.status() {
+                StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => IgdbError::Auth(response.status()),
+                status => IgdbError::from_status(status),
+            }.into());
+        }
+
+        let token: TokenResponse = response.json().await.map_err(IgdbError::Request)?;
+        let lifetime = Duration::from_secs(token.expires_in);
+        let refresh_at = Instant::now() + lifetime.saturating_sub(TOKEN_REFRESH_MARGIN.min(lifetime / 2));
+        tracing::info!("Obtained IGDB access token (expires in {}s)", token.expires_in);
+
+        *cached = Some(CachedToken {
+            access_token: token.access_token.clone(),
+            refresh_at,
+        });
+
+        Ok(token.access_token)
+    }
+
+    // Drop the cached token so the next request fetches a fresh one, unless another
+    // request already replaced it
+    async fn invalidate_token(&self, rejected: &str) {
+        let mut cached = self.token.lock().await;
+        if cached.as_ref().is_some_and(|token| token.access_token == rejected) {
+            *cached = None;
+        }
+    }
+
+    // Extract common request logic to avoid duplication
+    async fn make_igdb_request(&self, body: String) -> Result<Vec<IgdbGame>> {
+        let mut refreshed_token = false;
+        let mut retries = 0;
+
+        loop {
+            let access_token = self.access_token().await?;
+
+            let permit = self.concurrency.acquire().await?;
+            self.rate_limiter.acquire().await;
+
+            let response = self.client
+                .post(format!("{}/games", self.api_url))
+                .header("Client-ID", &self.client_id)
+                .header("Authorization", format!("Bearer {}", access_token))
+                .header("Content-Type", "application/json")
+                .body(body.clone())
+                .send()
+                .await
+                .map_err(IgdbError::Request)?;
+            let status = response.status();
+
+            // A revoked or prematurely expired token: get a new one and try once more
+            if status == StatusCode::UNAUTHORIZED && self.client_secret.is_some() && !refreshed_token {
+                drop(permit);
+                tracing::warn!("IGDB rejected the access token, refreshing");
+                self.invalidate_token(&access_token).await;
+                refreshed_token = true;
+                continue;
+            }
+
+            if (status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()) && retries < MAX_RETRIES {
+                drop(permit);
+                let delay = retry_after(&response).unwrap_or_else(|| backoff_delay(retries));
+                tracing::warn!("IGDB returned {}, retrying in {:?}", status, delay);
+                tokio::time::sleep(delay).await;
+                retries += 1;
+                continue;
+            }
+
+            if !status.is_success() {
+                return Err(IgdbError::from_status(status).into());
+            }
+
+            let games: Vec<IgdbGame> = response.json().await.map_err(IgdbError::Request)?;
+            return Ok(games);
+        }
+    }
+
+    async fn cached_request(&self, cache_key: String, body: String) -> Result<Vec<IgdbGame>> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.make_igdb_request(body).await,
+        };
+
+        let cached = cache.db.get_igdb_cache(&cache_key).await.unwrap_or_else(|e| {
+            tracing::warn!("Failed to read IGDB cache: {}", e);
+            None
+        });
+
+        let mut expired = None;
+        if let Some((response, fetched_at)) = cached {
+            let age = (Utc::now() - fetched_at).to_std().unwrap_or_default();
+            match serde_json::from_str::<Vec<IgdbGame>>(&response) {
+                Ok(games) if age < cache.ttl => return Ok(games),
+                Ok(games) if age < cache.ttl + cache.stale_ttl => {
+                    self.revalidate(cache, cache_key, body);
+                    return Ok(games);
+                }
+                Ok(games) => expired = Some(games),
+                Err(e) => tracing::warn!("Discarding unreadable IGDB cache entry {}: {}", cache_key, e),
+            }
+        }
+
+        match self.fetch_and_store(cache, &cache_key, body).await {
+            Ok(games) => Ok(games),
+            // Better an old answer than none while IGDB is unreachable
+            Err(e) => match expired {
+                Some(games) => {
+                    tracing::warn!("IGDB request failed, serving expired cache entry {}: {}", cache_key, e);
+                    Ok(games)
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    async fn fetch_and_store(&self, cache: &IgdbCache, cache_key: &str, body: String) -> Result<Vec<IgdbGame>> {
+        let games = self.make_igdb_request(body).await?;
+
+        match serde_json::to_string(&games) {
+            Ok(response) => {
+                if let Err(e) = cache.db.put_igdb_cache(cache_key, &response).await {
+                    tracing::warn!("Failed to write IGDB cache: {}", e);
+                }
+            }
+            Err(e) => tracing::warn!("Failed to serialize IGDB response for cache: {}", e),
         }
 
-        let games: Vec<IgdbGame> = response.json().await?;
         Ok(games)
     }
 
+    fn revalidate(&self, cache: &IgdbCache, cache_key: String, body: String) {
+        // One background refresh per key at a time
+        if !cache.refreshing.lock().unwrap().insert(cache_key.clone()) {
+            return;
+        }
+
+        let client = self.clone();
+        let cache = cache.clone();
+        tokio::spawn(async move {
+            if let Err(e) = client.fetch_and_store(&cache, &cache_key, body).await {
+                tracing::warn!("Background IGDB cache refresh failed for {}: {}", cache_key, e);
+            }
+            cache.refreshing.lock().unwrap().remove(&cache_key);
+        });
+    }
+
     pub async fn search_games(&self, query: &str, limit: u32) -> Result<Vec<IgdbGame>> {
+        let query = normalize_search(query);
+        let cache_key = format!("search:{}:{}", limit, query);
+
+        self.cached_request(cache_key, search_body(&query, limit)).await
+    }
+
+    pub async fn get_game_by_id(&self, igdb_id: i64) -> Result<Option<IgdbGame>> {
         let body = format!(
             r#"
-            search "{}";
             fields id,name,summary,storyline,rating,first_release_date,
                    cover.url,screenshots.url,genres.name,platforms.name,
                    involved_companies.company.name,involved_companies.developer,
                    involved_companies.publisher;
-            limit {};
+            where id = {};
             "#,
-            query, limit
+            igdb_id
         );
 
-        self.make_igdb_request(body).await
+        let mut games = self.cached_request(format!("game:{}", igdb_id), body).await?;
+        Ok(games.pop())
     }
+}
 
-    pub async fn get_game_by_id(&self, igdb_id: i64) -> Result<Option<IgdbGame>> {
-        let body = format!(
-            r#"
+#[async_trait]
+impl MetadataProvider for IgdbClient {
+    fn id(&self) -> &'static str {
+        "igdb"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "IGDB"
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            search: true,
+            fetch_by_id: true,
+            screenshots: true,
+            storyline: true,
+            ratings: true,
+        }
+    }
+
+    async fn search(&self, query: &str, limit: u32) -> Result<Vec<GameMetadata>> {
+        let games = self.search_games(query, limit).await?;
+        Ok(games.into_iter().map(to_metadata).collect())
+    }
+
+    async fn fetch(&self, external_id: &str) -> Result<Option<GameMetadata>> {
+        let igdb_id = external_id
+            .parse::<i64>()
+            .map_err(|_| anyhow!("invalid IGDB id: {}", external_id))?;
+        let game = self.get_game_by_id(igdb_id).await?;
+        Ok(game.map(to_metadata))
+    }
+}
+
+// Honour a Retry-After given in seconds, within reason
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(|secs| Duration::from_secs(secs).min(MAX_RETRY_DELAY))
+}
+
+// Exponential backoff plus up to the same again in random jitter, so parallel retries spread out
+fn backoff_delay(retries: u32) -> Duration {
+    let base = RETRY_BASE_DELAY * 2u32.pow(retries);
+    base + base.mul_f64(rand::random::<f64>())
+}
+
+fn to_metadata(game: IgdbGame) -> GameMetadata {
+    let companies = game.involved_companies.unwrap_or_default();
+
+    GameMetadata {
+        provider: "igdb".to_string(),
+        external_id: game.id.to_string(),
+        name: game.name,
+        summary: game.summary,
+        storyline: game.storyline,
+        rating: game.rating,
+        release_date: game.first_release_date.and_then(|timestamp| DateTime::from_timestamp(timestamp, 0)),
+        cover_url: game
+            .cover
+            .map(|cover| format!("https:{}", cover.url.replace("t_thumb", "t_cover_big"))),
+        screenshots: game
+            .screenshots
+            .unwrap_or_default()
+            .into_iter()
+            .map(|s| format!("https:{}", s.url.replace("t_thumb", "t_screenshot_med")))
+            .collect(),
+        genres: game.genres.unwrap_or_default().into_iter().map(|g| g.name).collect(),
+        platforms: game.platforms.unwrap_or_default().into_iter().map(|p| p.name).collect(),
+        developer: companies.iter().find(|c| c.developer).map(|c| c.company.name.clone()),
+        publisher: companies.iter().find(|c| c.publisher).map(|c| c.company.name.clone()),
+    }
+}
+
+// "Zelda ", "zelda" and "ZELDA" are the same search as far as the cache is concerned. Quotes
+// and backslashes are dropped: the query ends up inside an Apicalypse string, and titles
+// taken from file names must not be able to close it and add clauses of their own.
+fn normalize_search(query: &str) -> String {
+    query
+        .replace(['"', '\\'], " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn search_body(query: &str, limit: u32) -> String {
+    format!(
+        r#"
+            search "{}";
             fields id,name,summary,storyline,rating,first_release_date,
                    cover.url,screenshots.url,genres.name,platforms.name,
                    involved_companies.company.name,involved_companies.developer,
                    involved_companies.publisher;
-            where id = {};
+            limit {};
             "#,
-            igdb_id
-        );
+        normalize_search(query), limit
+    )
+}
 
-        let mut games = self.make_igdb_request(body).await?;
-        Ok(games.pop())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_query_cannot_break_out_of_its_string() {
+        let body = search_body(r#"Say "Hi"; where id = 1; limit 500; \"#, 10);
+
+        assert!(body.contains(r#"search "say hi ; where id = 1; limit 500;";"#), "{}", body);
+        assert_eq!(body.matches('"').count(), 2);
+        assert!(!body.contains('\\'));
+        assert!(body.contains("limit 10;"));
+    }
+
+    #[test]
+    fn search_query_is_normalized() {
+        assert_eq!(normalize_search("  The   Legend of\tZELDA "), "the legend of zelda");
+        assert_eq!(normalize_search(r#"Baldur's Gate "Enhanced""#), "baldur's gate enhanced");
     }
 }
@@ -0,0 +1,87 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use crate::{
+    handlers::{AppState, ApiResponse},
+    jobs,
+    models::{Job, JobListQuery, ScheduledTaskStatus},
+    permissions::{ManageJobs, RequirePermission},
+};
+
+const DEFAULT_JOB_LIMIT: i64 = 50;
+const MAX_JOB_LIMIT: i64 = 500;
+
+// Most recent jobs first, optionally filtered by status and kind
+pub async fn list_jobs(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageJobs>,
+    Query(query): Query<JobListQuery>,
+) -> Result<Json<ApiResponse<Vec<Job>>>, StatusCode> {
+    let limit = query.limit.unwrap_or(DEFAULT_JOB_LIMIT).clamp(1, MAX_JOB_LIMIT);
+
+    match state.db.get_jobs(query.status.as_deref(), query.kind.as_deref(), limit).await {
+        Ok(jobs) => Ok(Json(ApiResponse::success(jobs))),
+        Err(e) => {
+            tracing::error!("Failed to list jobs: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn get_job(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageJobs>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<Job>>, StatusCode> {
+    match state.db.get_job(&id).await {
+        Ok(Some(job)) => Ok(Json(ApiResponse::success(job))),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get job: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// Queued jobs are cancelled outright; running jobs stop at their next checkpoint
+pub async fn cancel_job(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageJobs>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<Job>>, StatusCode> {
+    match state.db.get_job(&id).await {
+        Ok(Some(job)) if job.status == "queued" || job.status == "running" => {}
+        Ok(Some(_)) => return Err(StatusCode::CONFLICT),
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get job: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    match jobs::cancel(&state, &id).await {
+        Ok(Some(job)) => Ok(Json(ApiResponse::success(job))),
+        // Finished between the check above and the cancel
+        Ok(None) => Err(StatusCode::CONFLICT),
+        Err(e) => {
+            tracing::error!("Failed to cancel job: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// Recurring maintenance tasks with their schedule and last outcome
+pub async fn list_scheduled_tasks(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageJobs>,
+) -> Result<Json<ApiResponse<Vec<ScheduledTaskStatus>>>, StatusCode> {
+    match state.scheduler.status(&state).await {
+        Ok(tasks) => Ok(Json(ApiResponse::success(tasks))),
+        Err(e) => {
+            tracing::error!("Failed to list scheduled tasks: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
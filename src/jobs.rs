@@ -0,0 +1,276 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::sync::Notify;
+use crate::{
+    handlers::AppState,
+    manifest, matcher, media,
+    models::Job,
+};
+
+// Idle workers look for newly due jobs (e.g. delayed retries) at least this often
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// Running jobs write their progress back to the database at most this often
+const PROGRESS_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+// First retry after 30s, then 60s, 120s, ...
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(30);
+
+// What a job does. Stored as JSON in jobs.payload; the tag doubles as jobs.kind.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobPayload {
+    ScanLibrary,
+    GenerateManifest { game_id: String },
+    MirrorMedia { game_id: String },
+    MatchMetadata { game_id: String },
+    RefreshMetadata { game_id: String },
+}
+
+impl JobPayload {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            JobPayload::ScanLibrary => "scan_library",
+            JobPayload::GenerateManifest { .. } => "generate_manifest",
+            JobPayload::MirrorMedia { .. } => "mirror_media",
+            JobPayload::MatchMetadata { .. } => "match_metadata",
+            JobPayload::RefreshMetadata { .. } => "refresh_metadata",
+        }
+    }
+
+    // Scans are cheap to re-run by hand; anything talking to the network gets a few tries
+    fn max_attempts(&self) -> i64 {
+        match self {
+            JobPayload::ScanLibrary => 1,
+            JobPayload::GenerateManifest { .. } => 2,
+            JobPayload::MirrorMedia { .. } | JobPayload::MatchMetadata { .. } | JobPayload::RefreshMetadata { .. } => 3,
+        }
+    }
+}
+
+// Latest progress percentage and message, not yet written to the database
+type PendingProgress = Option<(f64, Option<String>)>;
+
+// Shared with a running job so it can report progress and notice cancellation,
+// including from inside spawn_blocking
+#[derive(Clone, Default)]
+pub struct JobContext {
+    cancelled: Arc<AtomicBool>,
+    progress: Arc<Mutex<PendingProgress>>,
+}
+
+impl JobContext {
+    pub fn set_progress(&self, percent: f64, message: Option<String>) {
+        *self.progress.lock().unwrap() = Some((percent.clamp(0.0, 100.0), message));
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn check_cancelled(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(anyhow!("job cancelled"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn take_progress(&self) -> PendingProgress {
+        self.progress.lock().unwrap().take()
+    }
+}
+
+#[derive(Default)]
+pub struct JobQueue {
+    wake: Notify,
+    running: Mutex<HashMap<String, JobContext>>,
+}
+
+impl JobQueue {
+    // Flag a running job; it stops at its next cancellation check
+    fn cancel_running(&self, job_id: &str) {
+        if let Some(context) = self.running.lock().unwrap().get(job_id) {
+            context.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+// Queue a job. An identical job that is still waiting to run is returned instead of a duplicate;
+// one queued while an identical job runs starts only after that one has finished.
+pub async fn enqueue(state: &AppState, payload: JobPayload, created_by: Option<&str>) -> Result<Job> {
+    let job = state
+        .db
+        .create_job(payload.kind(), &serde_json::to_string(&payload)?, payload.max_attempts(), created_by)
+        .await?;
+    state.jobs.wake.notify_one();
+    Ok(job)
+}
+
+pub async fn cancel(state: &AppState, job_id: &str) -> Result<Option<Job>> {
+    let job = state.db.request_job_cancel(job_id).await?;
+    match job.as_ref().map(|job| job.status.as_str()) {
+        Some("running") => state.jobs.cancel_running(job_id),
+        // Never started, so nothing else will clean up after it
+        Some("cancelled") => {
+            if let Some(Ok(payload)) = job.as_ref().map(|job| serde_json::from_str::<JobPayload>(&job.payload)) {
+                on_failure(state, &payload, "cancelled").await;
+            }
+        }
+        _ => {}
+    }
+    Ok(job)
+}
+
+pub async fn start_workers(state: &AppState, count: usize) -> Result<()> {
+    // Jobs that were running when the server stopped start over
+    let requeued = state.db.requeue_interrupted_jobs().await?;
+    if requeued > 0 {
+        tracing::info!("Requeued {} interrupted job(s)", requeued);
+    }
+
+    for worker in 0..count {
+        let state = state.clone();
+        tokio::spawn(async move {
+            loop {
+                match state.db.claim_next_job().await {
+                    Ok(Some(job)) => run_job(&state, job).await,
+                    Ok(None) => {
+                        tokio::select! {
+                            _ = state.jobs.wake.notified() => {}
+                            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                        }
+                    }
+                    Err(e) => {
+                        tracing::error!("Job worker {} failed to claim a job: {}", worker, e);
+                        tokio::time::sleep(POLL_INTERVAL).await;
+                    }
+                }
+            }
+        });
+    }
+
+    Ok(())
+}
+
+async fn run_job(state: &AppState, job: Job) {
+    let payload: JobPayload = match serde_json::from_str(&job.payload) {
+        Ok(payload) => payload,
+        Err(e) => {
+            tracing::error!("Job {} has an unreadable payload: {}", job.id, e);
+            let error = format!("unreadable payload: {}", e);
+            if let Err(e) = state.db.finish_job(&job.id, "failed", None, Some(&error)).await {
+                tracing::error!("Failed to record job {} failure: {}", job.id, e);
+            }
+            return;
+        }
+    };
+
+    tracing::info!("Running job {} ({}), attempt {}/{}", job.id, job.kind, job.attempts, job.max_attempts);
+
+    let context = JobContext::default();
+    state.jobs.running.lock().unwrap().insert(job.id.clone(), context.clone());
+
+    // A cancel that arrived between claiming the job and registering it above
+    if let Ok(Some(current)) = state.db.get_job(&job.id).await {
+        if current.cancel_requested {
+            context.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    let flusher = {
+        let state = state.clone();
+        let context = context.clone();
+        let job_id = job.id.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(PROGRESS_FLUSH_INTERVAL).await;
+                if let Some((progress, message)) = context.take_progress() {
+                    if let Err(e) = state.db.update_job_progress(&job_id, progress, message.as_deref()).await {
+                        tracing::warn!("Failed to record progress for job {}: {}", job_id, e);
+                    }
+                }
+            }
+        })
+    };
+
+    let outcome = execute(state, &payload, &context).await;
+
+    flusher.abort();
+    state.jobs.running.lock().unwrap().remove(&job.id);
+
+    let recorded = match outcome {
+        Ok(result) => {
+            tracing::info!("Job {} ({}) completed", job.id, job.kind);
+            state.db.finish_job(&job.id, "completed", Some(&result.to_string()), None).await
+        }
+        Err(_) if context.is_cancelled() => {
+            tracing::info!("Job {} ({}) cancelled", job.id, job.kind);
+            on_failure(state, &payload, "cancelled").await;
+            state.db.finish_job(&job.id, "cancelled", None, None).await
+        }
+        Err(e) if job.attempts < job.max_attempts => {
+            let delay = RETRY_BASE_DELAY * 2u32.pow((job.attempts - 1).max(0) as u32);
+            tracing::warn!("Job {} ({}) failed, retrying in {:?}: {}", job.id, job.kind, delay, e);
+            state.db.retry_job(&job.id, &e.to_string(), delay).await
+        }
+        Err(e) => {
+            tracing::error!("Job {} ({}) failed: {}", job.id, job.kind, e);
+            on_failure(state, &payload, &e.to_string()).await;
+            state.db.finish_job(&job.id, "failed", None, Some(&e.to_string())).await
+        }
+    };
+
+    if let Err(e) = recorded {
+        tracing::error!("Failed to record outcome of job {}: {}", job.id, e);
+    }
+}
+
+async fn execute(state: &AppState, payload: &JobPayload, context: &JobContext) -> Result<serde_json::Value> {
+    match payload {
+        JobPayload::ScanLibrary => {
+            context.set_progress(0.0, Some("Scanning library roots".to_string()));
+            let report = state.library_scanner.scan(&state.db).await?;
+            for game_id in &report.changed_game_ids {
+                manifest::queue_generation(state, game_id.clone()).await;
+            }
+            for game_id in &report.added_game_ids {
+                matcher::queue_match(state, game_id.clone()).await;
+            }
+            Ok(serde_json::to_value(report)?)
+        }
+        JobPayload::GenerateManifest { game_id } => {
+            let files = manifest::generate(state, game_id, context).await?;
+            Ok(serde_json::json!({ "files": files }))
+        }
+        JobPayload::MirrorMedia { game_id } => {
+            let mirrored = media::mirror_game(state, game_id, context).await?;
+            Ok(serde_json::json!({ "mirrored": mirrored }))
+        }
+        JobPayload::MatchMetadata { game_id } => {
+            let outcome = matcher::match_game(state, game_id).await?;
+            Ok(serde_json::json!({ "outcome": outcome }))
+        }
+        JobPayload::RefreshMetadata { game_id } => {
+            let outcome = matcher::refresh_game(state, game_id).await?;
+            Ok(serde_json::json!({ "outcome": outcome }))
+        }
+    }
+}
+
+// Bookkeeping outside the jobs table once a job has used up its attempts or was cancelled
+async fn on_failure(state: &AppState, payload: &JobPayload, error: &str) {
+    if let JobPayload::GenerateManifest { game_id } = payload {
+        if let Err(e) = state.db.set_manifest_status(game_id, "failed", Some(error.to_string())).await {
+            tracing::error!("Failed to record manifest failure for {}: {}", game_id, e);
+        }
+    }
+}
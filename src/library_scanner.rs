@@ -0,0 +1,262 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+use tokio::sync::Mutex;
+use crate::{database::Database, manifest, models::CreateGameRequest};
+
+// File types treated as a complete game when they sit directly inside a library root
+const INSTALLER_EXTENSIONS: &[&str] = &[
+    "exe", "msi", "zip", "7z", "rar", "iso", "sh", "run", "appimage", "dmg", "pkg", "deb", "rpm",
+    "tgz", "gz", "xz", "bz2",
+];
+
+#[derive(Debug)]
+struct DiscoveredEntry {
+    path: String,
+    name: String,
+    size: i64,
+    fingerprint: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ScanReport {
+    pub roots: Vec<String>,
+    pub discovered: usize,
+    pub added: usize,
+    pub updated: usize,
+    pub restored: usize,
+    pub marked_unavailable: usize,
+    pub errors: Vec<String>,
+    // Games whose files were added or changed and need their manifest rebuilt
+    #[serde(skip)]
+    pub changed_game_ids: Vec<String>,
+    // Newly created games, which still need to be matched to metadata
+    #[serde(skip)]
+    pub added_game_ids: Vec<String>,
+}
+
+pub struct LibraryScanner {
+    roots: Vec<PathBuf>,
+    scan_lock: Mutex<()>,
+}
+
+impl LibraryScanner {
+    pub fn new(roots: Vec<PathBuf>) -> Self {
+        Self {
+            roots,
+            scan_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    pub async fn scan(&self, db: &Database) -> Result<ScanReport> {
+        // Only one scan at a time, otherwise two passes could both insert the same new folder
+        let _guard = self.scan_lock.lock().await;
+
+        let roots = self.roots.clone();
+        let (discovered, errors) = tokio::task::spawn_blocking(move || discover(&roots)).await?;
+
+        let mut report = ScanReport {
+            roots: self.roots.iter().map(|root| root.to_string_lossy().to_string()).collect(),
+            discovered: discovered.len(),
+            errors,
+            ..Default::default()
+        };
+
+        let existing: HashMap<String, _> = db
+            .get_games_with_files()
+            .await?
+            .into_iter()
+            .filter_map(|game| game.file_path.clone().map(|path| (path, game)))
+            .collect();
+
+        let manifests: HashMap<String, _> = db
+            .get_all_manifests()
+            .await?
+            .into_iter()
+            .map(|manifest| (manifest.game_id.clone(), manifest))
+            .collect();
+
+        // Older builds of an existing game live alongside it on disk and must not become new games
+        let build_paths: HashSet<String> = db.get_build_file_paths().await?.into_iter().collect();
+
+        for entry in &discovered {
+            match existing.get(&entry.path) {
+                Some(game) => {
+                    // Files replaced in place can keep the total size; the manifest's fingerprint
+                    // catches that. A manifest still pending will read the current files anyway.
+                    let manifest_stale = match manifests.get(&game.id) {
+                        Some(manifest) if manifest.status == "pending" => false,
+                        Some(manifest) => manifest.fingerprint != entry.fingerprint,
+                        None => true,
+                    };
+                    if game.file_size == Some(entry.size) && !game.missing_on_disk && !manifest_stale {
+                        continue;
+                    }
+                    // Only games this scanner hid come back; an admin's "unavailable" stays
+                    if game.missing_on_disk {
+                        db.set_game_missing_on_disk(&game.id, Some(entry.size), false).await?;
+                        report.restored += 1;
+                    } else {
+                        db.update_game_file_size(&game.id, Some(entry.size)).await?;
+                        report.updated += 1;
+                    }
+                    report.changed_game_ids.push(game.id.clone());
+                }
+                None if build_paths.contains(&entry.path) => {}
+                None => {
+                    let game = db.create_game(CreateGameRequest {
+                        name: entry.name.clone(),
+                        igdb_id: None,
+                        file_path: Some(entry.path.clone()),
+                        file_size: Some(entry.size),
+                    })
+                    .await?;
+                    report.changed_game_ids.push(game.id.clone());
+                    report.added_game_ids.push(game.id);
+                    report.added += 1;
+                }
+            }
+        }
+
+        // Anything under a root that has vanished from disk is hidden from the store, not deleted
+        for (path, game) in &existing {
+            if !game.is_available || !self.is_under_root(path) || Path::new(path).exists() {
+                continue;
+            }
+            db.set_game_missing_on_disk(&game.id, game.file_size, true).await?;
+            report.marked_unavailable += 1;
+        }
+
+        tracing::info!(
+            "Library scan finished: {} discovered, {} added, {} updated, {} restored, {} marked unavailable",
+            report.discovered,
+            report.added,
+            report.updated,
+            report.restored,
+            report.marked_unavailable
+        );
+
+        Ok(report)
+    }
+
+    fn is_under_root(&self, path: &str) -> bool {
+        self.roots.iter().any(|root| Path::new(path).starts_with(root))
+    }
+}
+
+fn discover(roots: &[PathBuf]) -> (Vec<DiscoveredEntry>, Vec<String>) {
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+
+    for root in roots {
+        let dir = match fs::read_dir(root) {
+            Ok(dir) => dir,
+            Err(e) => {
+                tracing::warn!("Failed to read library root {}: {}", root.display(), e);
+                errors.push(format!("{}: {}", root.display(), e));
+                continue;
+            }
+        };
+
+        for entry in dir.flatten() {
+            let path = entry.path();
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if file_name.starts_with('.') {
+                continue;
+            }
+
+            let file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(e) => {
+                    errors.push(format!("{}: {}", path.display(), e));
+                    continue;
+                }
+            };
+
+            let (name, size) = if file_type.is_dir() {
+                (file_name.clone(), directory_size(&path))
+            } else if file_type.is_file() && is_installer(&path) {
+                let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                (strip_installer_extension(&file_name), size)
+            } else {
+                continue;
+            };
+
+            entries.push(DiscoveredEntry {
+                path: path.to_string_lossy().to_string(),
+                name: clean_title(&name),
+                size: size as i64,
+                fingerprint: manifest::fingerprint(&path).ok(),
+            });
+        }
+    }
+
+    (entries, errors)
+}
+
+// Size of a single installer, or the total of everything inside a game folder
+pub fn path_size(path: &Path) -> std::io::Result<u64> {
+    let metadata = fs::metadata(path)?;
+    if metadata.is_dir() {
+        Ok(directory_size(path))
+    } else {
+        Ok(metadata.len())
+    }
+}
+
+fn directory_size(path: &Path) -> u64 {
+    let dir = match fs::read_dir(path) {
+        Ok(dir) => dir,
+        Err(_) => return 0,
+    };
+
+    dir.flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => directory_size(&entry.path()),
+            Ok(file_type) if file_type.is_file() => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            _ => 0,
+        })
+        .sum()
+}
+
+fn is_installer(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| INSTALLER_EXTENSIONS.contains(&ext.as_str()))
+}
+
+fn strip_installer_extension(file_name: &str) -> String {
+    let lower = file_name.to_lowercase();
+    for compound in [".tar.gz", ".tar.xz", ".tar.bz2"] {
+        if lower.ends_with(compound) {
+            return file_name[..file_name.len() - compound.len()].to_string();
+        }
+    }
+
+    Path::new(file_name)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| file_name.to_string())
+}
+
+// Turn "setup_some_game" style names into something readable; the admin can still rename later
+fn clean_title(raw: &str) -> String {
+    let trimmed = raw
+        .strip_prefix("setup_")
+        .or_else(|| raw.strip_prefix("Setup_"))
+        .unwrap_or(raw);
+
+    trimmed
+        .replace('_', " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
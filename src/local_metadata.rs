@@ -0,0 +1,123 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use crate::{
+    metadata_provider::{MetadataProvider, ProviderCapabilities},
+    models::GameMetadata,
+};
+
+// One hand-written <id>.json file per game inside the metadata directory
+#[derive(Debug, Deserialize)]
+struct LocalGameFile {
+    name: String,
+    summary: Option<String>,
+    storyline: Option<String>,
+    rating: Option<f64>,
+    release_date: Option<DateTime<Utc>>,
+    cover_url: Option<String>,
+    #[serde(default)]
+    screenshots: Vec<String>,
+    #[serde(default)]
+    genres: Vec<String>,
+    #[serde(default)]
+    platforms: Vec<String>,
+    developer: Option<String>,
+    publisher: Option<String>,
+}
+
+pub struct LocalMetadataProvider {
+    dir: PathBuf,
+}
+
+impl LocalMetadataProvider {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    async fn read(&self, path: &Path) -> Result<Option<GameMetadata>> {
+        let external_id = match path.file_stem() {
+            Some(stem) => stem.to_string_lossy().to_string(),
+            None => return Ok(None),
+        };
+
+        let data = match tokio::fs::read(path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let file: LocalGameFile = serde_json::from_slice(&data)
+            .map_err(|e| anyhow!("invalid metadata file {}: {}", path.display(), e))?;
+
+        Ok(Some(GameMetadata {
+            provider: "local".to_string(),
+            external_id,
+            name: file.name,
+            summary: file.summary,
+            storyline: file.storyline,
+            rating: file.rating,
+            release_date: file.release_date,
+            cover_url: file.cover_url,
+            screenshots: file.screenshots,
+            genres: file.genres,
+            platforms: file.platforms,
+            developer: file.developer,
+            publisher: file.publisher,
+        }))
+    }
+}
+
+#[async_trait]
+impl MetadataProvider for LocalMetadataProvider {
+    fn id(&self) -> &'static str {
+        "local"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Local files"
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            search: true,
+            fetch_by_id: true,
+            screenshots: true,
+            storyline: true,
+            ratings: true,
+        }
+    }
+
+    async fn search(&self, query: &str, limit: u32) -> Result<Vec<GameMetadata>> {
+        let needle = query.to_lowercase();
+        let mut results = Vec::new();
+        let mut dir = tokio::fs::read_dir(&self.dir).await?;
+
+        while let Some(entry) = dir.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+
+            match self.read(&path).await {
+                Ok(Some(game)) if game.name.to_lowercase().contains(&needle) => results.push(game),
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Skipping local metadata: {}", e),
+            }
+        }
+
+        results.sort_by(|a, b| a.name.cmp(&b.name));
+        results.truncate(limit as usize);
+        Ok(results)
+    }
+
+    async fn fetch(&self, external_id: &str) -> Result<Option<GameMetadata>> {
+        // The id names a file, so refuse anything that could walk out of the directory
+        if external_id.is_empty() || external_id.contains(['/', '\\']) || external_id.starts_with('.') {
+            return Err(anyhow!("invalid local metadata id: {}", external_id));
+        }
+
+        self.read(&self.dir.join(format!("{}.json", external_id))).await
+    }
+}
@@ -1,27 +1,55 @@
 mod models;
 mod database;
 mod igdb_client;
+mod metadata_provider;
+mod rawg_client;
+mod local_metadata;
 mod handlers;
 mod auth;
 mod auth_service;
+mod password_policy;
+mod oidc;
+mod permissions;
 mod auth_handlers;
+mod access_handlers;
 mod middleware;
 mod user_handlers;
+mod downloads;
+mod library_scanner;
+mod manifest;
+mod jobs;
+mod scheduler;
+mod job_handlers;
+mod matcher;
+mod media;
+mod catalog;
+mod cli;
 
 use axum::{
-    routing::{get, post, delete},
+    routing::{get, post, put, delete},
     Router,
     middleware::from_fn_with_state,
 };
-use tower_http::{cors::CorsLayer, services::ServeDir};
+use axum::http::{header, HeaderValue};
+use tower_http::{cors::CorsLayer, services::ServeDir, set_header::SetResponseHeaderLayer};
 use std::sync::Arc;
-use tracing_subscriber;
 
 use crate::{
     database::Database,
     igdb_client::IgdbClient,
+    rawg_client::RawgClient,
+    local_metadata::LocalMetadataProvider,
+    metadata_provider::{MetadataProvider, MetadataProviders},
+    auth::{LoginThrottle, SessionLifetimes},
     auth_service::AuthService,
+    password_policy::PasswordPolicy,
+    oidc::{OidcClient, OidcConfig},
     handlers::{AppStateInner, AppState},
+    library_scanner::LibraryScanner,
+    jobs::{JobPayload, JobQueue},
+    scheduler::{MaintenanceTask, Scheduler},
+    media::MediaMirror,
+    models::AccessEffect,
 };
 
 #[tokio::main]
@@ -37,8 +65,128 @@ async fn main() -> anyhow::Result<()> {
         .unwrap_or_else(|_| "sqlite:./games.db".to_string());
     let igdb_client_id = std::env::var("IGDB_CLIENT_ID")
         .unwrap_or_else(|_| "your_client_id".to_string());
-    let igdb_access_token = std::env::var("IGDB_ACCESS_TOKEN")
-        .unwrap_or_else(|_| "your_access_token".to_string());
+    let igdb_client_secret = std::env::var("IGDB_CLIENT_SECRET").ok().filter(|secret| !secret.is_empty());
+    let igdb_access_token = std::env::var("IGDB_ACCESS_TOKEN").ok().filter(|token| !token.is_empty());
+    let igdb_token_url = std::env::var("IGDB_TOKEN_URL")
+        .unwrap_or_else(|_| "https://id.twitch.tv/oauth2/token".to_string());
+    let igdb_api_url = std::env::var("IGDB_API_URL")
+        .unwrap_or_else(|_| "https://api.igdb.com/v4".to_string());
+    let igdb_cache_ttl = std::env::var("IGDB_CACHE_TTL_SECS")
+        .unwrap_or_else(|_| "86400".to_string())
+        .parse::<u64>()
+        .expect("IGDB_CACHE_TTL_SECS must be a number of seconds");
+    let igdb_cache_stale = std::env::var("IGDB_CACHE_STALE_SECS")
+        .unwrap_or_else(|_| "604800".to_string())
+        .parse::<u64>()
+        .expect("IGDB_CACHE_STALE_SECS must be a number of seconds");
+    let metadata_provider = std::env::var("METADATA_PROVIDER")
+        .unwrap_or_else(|_| "igdb".to_string());
+    let rawg_api_key = std::env::var("RAWG_API_KEY").ok().filter(|key| !key.is_empty());
+    let rawg_api_url = std::env::var("RAWG_API_URL")
+        .unwrap_or_else(|_| "https://api.rawg.io/api".to_string());
+    let local_metadata_dir = std::env::var_os("LOCAL_METADATA_DIR").map(std::path::PathBuf::from);
+    let media_dir = std::env::var("MEDIA_DIR")
+        .unwrap_or_else(|_| "./media".to_string());
+    let mirror_media = std::env::var("MIRROR_MEDIA")
+        .map(|value| value != "false" && value != "0")
+        .unwrap_or(true);
+    let library_roots: Vec<std::path::PathBuf> = std::env::var_os("LIBRARY_ROOTS")
+        .map(|roots| std::env::split_paths(&roots).filter(|root| !root.as_os_str().is_empty()).collect())
+        .unwrap_or_default();
+    let job_workers = std::env::var("JOB_WORKERS")
+        .unwrap_or_else(|_| "4".to_string())
+        .parse::<usize>()
+        .expect("JOB_WORKERS must be a number");
+    let metadata_stale_days = std::env::var("METADATA_STALE_DAYS")
+        .unwrap_or_else(|_| "30".to_string())
+        .parse::<u64>()
+        .expect("METADATA_STALE_DAYS must be a number of days");
+    let schedules: Vec<(MaintenanceTask, String)> = MaintenanceTask::ALL
+        .iter()
+        .map(|task| {
+            let spec = std::env::var(task.env_var()).unwrap_or_else(|_| task.default_schedule().to_string());
+            (*task, spec)
+        })
+        .collect();
+    let access_token_ttl = std::env::var("ACCESS_TOKEN_TTL_SECS")
+        .unwrap_or_else(|_| "900".to_string())
+        .parse::<i64>()
+        .expect("ACCESS_TOKEN_TTL_SECS must be a number of seconds");
+    let refresh_token_ttl = std::env::var("REFRESH_TOKEN_TTL_SECS")
+        .unwrap_or_else(|_| "2592000".to_string())
+        .parse::<i64>()
+        .expect("REFRESH_TOKEN_TTL_SECS must be a number of seconds");
+    let session_max_lifetime = std::env::var("SESSION_MAX_LIFETIME_SECS")
+        .unwrap_or_else(|_| "0".to_string())
+        .parse::<i64>()
+        .expect("SESSION_MAX_LIFETIME_SECS must be a number of seconds");
+    let login_max_failures = std::env::var("LOGIN_MAX_FAILURES")
+        .unwrap_or_else(|_| "5".to_string())
+        .parse::<i64>()
+        .expect("LOGIN_MAX_FAILURES must be a number");
+    let login_max_ip_failures = std::env::var("LOGIN_MAX_IP_FAILURES")
+        .unwrap_or_else(|_| "50".to_string())
+        .parse::<i64>()
+        .expect("LOGIN_MAX_IP_FAILURES must be a number");
+    let login_lockout = std::env::var("LOGIN_LOCKOUT_SECS")
+        .unwrap_or_else(|_| "900".to_string())
+        .parse::<i64>()
+        .expect("LOGIN_LOCKOUT_SECS must be a number of seconds");
+    let password_min_length = std::env::var("PASSWORD_MIN_LENGTH")
+        .unwrap_or_else(|_| "8".to_string())
+        .parse::<usize>()
+        .expect("PASSWORD_MIN_LENGTH must be a number");
+    let breached_passwords_file = std::env::var_os("BREACHED_PASSWORDS_FILE")
+        .filter(|path| !path.is_empty())
+        .map(std::path::PathBuf::from);
+    // Without these, per-address login limits see the proxy's address for every client
+    let trusted_proxies: Vec<std::net::IpAddr> = std::env::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|proxy| !proxy.is_empty())
+        .map(|proxy| proxy.parse().expect("TRUSTED_PROXIES must be a comma-separated list of IP addresses"))
+        .collect();
+    let default_game_access = match std::env::var("GAME_ACCESS_DEFAULT").as_deref() {
+        Ok("allow") | Err(_) => AccessEffect::Allow,
+        Ok("deny") => AccessEffect::Deny,
+        Ok(_) => panic!("GAME_ACCESS_DEFAULT must be allow or deny"),
+    };
+    // Sign-in with an OpenID Connect provider is enabled by setting OIDC_ISSUER
+    let oidc_config = std::env::var("OIDC_ISSUER").ok().filter(|issuer| !issuer.is_empty()).map(|issuer| {
+        let list = |name: &str| -> Vec<String> {
+            std::env::var(name)
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        OidcConfig {
+            issuer,
+            client_id: std::env::var("OIDC_CLIENT_ID").expect("OIDC_CLIENT_ID must be set when OIDC_ISSUER is"),
+            client_secret: std::env::var("OIDC_CLIENT_SECRET").ok().filter(|secret| !secret.is_empty()),
+            redirect_url: std::env::var("OIDC_REDIRECT_URL")
+                .expect("OIDC_REDIRECT_URL must be set when OIDC_ISSUER is"),
+            scopes: std::env::var("OIDC_SCOPES").unwrap_or_else(|_| "openid profile email groups".to_string()),
+            groups_claim: std::env::var("OIDC_GROUPS_CLAIM").unwrap_or_else(|_| "groups".to_string()),
+            admin_groups: list("OIDC_ADMIN_GROUPS"),
+            role_mapping: list("OIDC_ROLE_MAPPING")
+                .iter()
+                .map(|mapping| {
+                    let (group, role) = mapping
+                        .split_once('=')
+                        .expect("OIDC_ROLE_MAPPING must be a comma-separated list of group=role");
+                    (group.trim().to_string(), role.trim().to_string())
+                })
+                .collect(),
+            auto_create_users: std::env::var("OIDC_AUTO_CREATE_USERS")
+                .map(|value| value != "false" && value != "0")
+                .unwrap_or(true),
+            allowed_redirects: list("OIDC_ALLOWED_REDIRECTS"),
+        }
+    });
     let port = std::env::var("PORT")
         .unwrap_or_else(|_| "3000".to_string())
         .parse::<u16>()
@@ -48,47 +196,237 @@ async fn main() -> anyhow::Result<()> {
     let db = Database::new(&database_url).await?;
     tracing::info!("Database connected successfully");
 
+    // Initialize password policy
+    let password_policy = PasswordPolicy::new(password_min_length, breached_passwords_file.as_deref())?;
+    tracing::info!(
+        "Password policy: minimum {} characters, {} breached passwords loaded",
+        password_min_length,
+        password_policy.breached_count()
+    );
+
+    // Maintenance commands (e.g. `export-catalog games.json`) run once and exit instead of serving
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return cli::run(&db, password_policy, &args).await;
+    }
+
     // Initialize IGDB client
-    let igdb_client = IgdbClient::new(igdb_client_id, igdb_access_token);
+    if igdb_client_secret.is_none() {
+        tracing::warn!("IGDB_CLIENT_SECRET not set; using static IGDB_ACCESS_TOKEN, which will not be refreshed");
+    }
+    let igdb_client = Arc::new(IgdbClient::new(
+        igdb_client_id,
+        igdb_client_secret,
+        igdb_access_token,
+        igdb_token_url,
+        igdb_api_url,
+    ).with_cache(
+        db.clone(),
+        std::time::Duration::from_secs(igdb_cache_ttl),
+        std::time::Duration::from_secs(igdb_cache_stale),
+    ));
     tracing::info!("IGDB client initialized");
 
+    // Initialize metadata providers; IGDB is always available, the others when configured
+    let mut providers: Vec<Arc<dyn MetadataProvider>> = vec![igdb_client.clone()];
+    if let Some(api_key) = rawg_api_key {
+        providers.push(Arc::new(RawgClient::new(api_key, rawg_api_url)));
+    }
+    if let Some(dir) = local_metadata_dir {
+        providers.push(Arc::new(LocalMetadataProvider::new(dir)));
+    }
+    let metadata_providers = MetadataProviders::new(providers, metadata_provider);
+    tracing::info!(
+        "Metadata providers initialized (default: {})",
+        metadata_providers.default_provider().id()
+    );
+
     // Initialize auth service
-    let auth_service = AuthService::new(db.get_pool().clone());
+    let auth_service = AuthService::new(db.get_pool().clone()).with_lifetimes(SessionLifetimes {
+        access: chrono::Duration::seconds(access_token_ttl.max(1)),
+        refresh: chrono::Duration::seconds(refresh_token_ttl.max(1)),
+        max_session: (session_max_lifetime > 0).then(|| chrono::Duration::seconds(session_max_lifetime)),
+    }).with_password_policy(password_policy).with_login_throttle(LoginThrottle {
+        max_user_failures: login_max_failures.max(1),
+        max_ip_failures: login_max_ip_failures.max(1),
+        lockout: chrono::Duration::seconds(login_lockout.max(1)),
+    });
+    let auth_service = match oidc_config {
+        Some(config) => {
+            tracing::info!(
+                "OpenID Connect sign-in enabled with {} (creating users: {})",
+                config.issuer,
+                config.auto_create_users
+            );
+            auth_service.with_oidc(OidcClient::new(config))
+        }
+        None => auth_service,
+    };
     tracing::info!("Auth service initialized");
+    if auth_service.setup_required().await.unwrap_or(false) {
+        tracing::warn!(
+            "No user accounts yet: create the first admin with POST /api/setup or `game-library-server create-admin <username>`"
+        );
+    }
+
+    // Initialize library scanner
+    let library_scanner = LibraryScanner::new(library_roots);
+    tracing::info!("Library scanner initialized with {} root(s)", library_scanner.roots().len());
+
+    // Initialize media mirror
+    let media = MediaMirror::new(std::path::PathBuf::from(&media_dir), mirror_media);
+    tracing::info!("Media directory: {} (mirroring {})", media_dir, if mirror_media { "enabled" } else { "disabled" });
+
+    if !trusted_proxies.is_empty() {
+        tracing::info!("Client addresses taken from X-Forwarded-For behind {} trusted prox(ies)", trusted_proxies.len());
+    }
+
+    tracing::info!("Games without an access rule are {} by default", match default_game_access {
+        AccessEffect::Allow => "visible to everyone",
+        AccessEffect::Deny => "hidden",
+    });
+
+    // Initialize maintenance scheduler
+    let scheduler = Scheduler::new(schedules, std::time::Duration::from_secs(metadata_stale_days * 24 * 60 * 60))?;
+    tracing::info!("Scheduler initialized with {} enabled task(s)", scheduler.enabled_count());
 
     // Create application state
     let state: AppState = Arc::new(AppStateInner {
         db,
         igdb_client,
+        metadata_providers,
         auth_service,
+        library_scanner,
+        media,
+        jobs: JobQueue::default(),
+        scheduler,
+        default_game_access,
+        trusted_proxies,
     });
 
-    // Build the application router with multi-user game management
-    let app = Router::new()
-        // Public routes (no auth required)
+    jobs::start_workers(&state, job_workers.max(1)).await?;
+    tracing::info!("Started {} job worker(s)", job_workers.max(1));
+
+    scheduler::start(&state).await?;
+
+    // Pick up anything added to the library roots while the server was down
+    if !state.library_scanner.roots().is_empty() {
+        if let Err(e) = jobs::enqueue(&state, JobPayload::ScanLibrary, None).await {
+            tracing::error!("Failed to queue startup library scan: {}", e);
+        }
+    }
+
+    // Public routes (no auth required)
+    let public_routes = Router::new()
+        .route("/api/setup", get(auth_handlers::setup_status).post(auth_handlers::setup))
         .route("/api/auth/login", post(auth_handlers::login))
+        .route("/api/auth/refresh", post(auth_handlers::refresh))
+        .route("/api/auth/oidc", get(auth_handlers::oidc_status))
+        .route("/api/auth/oidc/login", get(auth_handlers::oidc_login))
+        .route("/api/auth/oidc/callback", get(auth_handlers::oidc_callback))
+        .route("/health", get(handlers::health_check));
 
-        // User routes (auth required)
+    // User routes (auth required)
+    let user_routes = Router::new()
         .route("/api/auth/me", get(auth_handlers::me))
         .route("/api/auth/logout", post(auth_handlers::logout))
+        .route("/api/auth/password", post(auth_handlers::change_password))
+        .route("/api/auth/sessions", get(auth_handlers::list_sessions))
+        .route("/api/auth/sessions/{id}", delete(auth_handlers::revoke_session))
+        .route("/api/auth/tokens", get(auth_handlers::list_access_tokens).post(auth_handlers::create_access_token))
+        .route("/api/auth/tokens/{id}", delete(auth_handlers::revoke_access_token))
+        .route("/api/auth/oidc/link", post(auth_handlers::oidc_link))
+        .route("/api/auth/oidc/link/{id}/confirm", post(auth_handlers::confirm_oidc_link))
+        .route("/api/auth/identities", get(auth_handlers::list_identities))
+        .route("/api/auth/identities/{id}", delete(auth_handlers::unlink_identity))
         .route("/api/store/games", get(user_handlers::get_store_games))
         .route("/api/user/library", get(user_handlers::get_user_library))
         .route("/api/user/library/{id}", get(user_handlers::get_user_game))
         .route("/api/user/games/{id}/install", post(user_handlers::install_game))
         .route("/api/user/games/{id}/uninstall", delete(user_handlers::uninstall_game))
-        .route_layer(from_fn_with_state(state.clone(), middleware::auth_middleware))
+        .route("/api/user/games/{id}/download", get(user_handlers::download_game))
+        .route("/api/user/games/{id}/files/{*path}", get(user_handlers::download_game_file))
+        .route("/api/user/games/{id}/manifest", get(user_handlers::get_game_manifest))
+        .route("/api/user/games/{id}/builds", get(user_handlers::get_game_builds))
+        .route_layer(from_fn_with_state(state.clone(), middleware::auth_middleware));
 
-        // Admin-only routes
+    // Admin routes; each handler checks the permission it needs
+    let admin_routes = Router::new()
         .route("/api/admin/users", get(auth_handlers::list_users).post(auth_handlers::create_user))
         .route("/api/admin/users/{id}", delete(auth_handlers::delete_user))
+        .route(
+            "/api/admin/users/{id}/sessions",
+            get(auth_handlers::list_user_sessions).delete(auth_handlers::revoke_user_sessions),
+        )
+        .route("/api/admin/users/{id}/unlock", post(auth_handlers::unlock_user))
+        .route("/api/admin/users/{id}/password-reset", post(auth_handlers::reset_user_password))
+        .route("/api/admin/users/{id}/roles", get(auth_handlers::list_user_roles))
+        .route(
+            "/api/admin/users/{id}/roles/{role_id}",
+            put(auth_handlers::assign_user_role).delete(auth_handlers::unassign_user_role),
+        )
+        .route("/api/admin/roles", get(auth_handlers::list_roles).post(auth_handlers::create_role))
+        .route("/api/admin/roles/{id}", put(auth_handlers::update_role).delete(auth_handlers::delete_role))
+        .route("/api/admin/permissions", get(auth_handlers::list_permissions))
+        .route("/api/admin/groups", get(access_handlers::list_groups).post(access_handlers::create_group))
+        .route("/api/admin/groups/{id}", delete(access_handlers::delete_group))
+        .route("/api/admin/groups/{id}/members", get(access_handlers::list_group_members))
+        .route(
+            "/api/admin/groups/{id}/members/{user_id}",
+            put(access_handlers::add_group_member).delete(access_handlers::remove_group_member),
+        )
+        .route(
+            "/api/admin/access-rules",
+            get(access_handlers::list_access_rules).post(access_handlers::create_access_rule),
+        )
+        .route("/api/admin/access-rules/{id}", delete(access_handlers::delete_access_rule))
         .route("/api/admin/games", get(handlers::get_games).post(handlers::create_game))
-        .route("/api/admin/games/{id}", get(handlers::get_game)) // Removed the .put(handlers::update_game)
+        .route(
+            "/api/admin/games/{id}",
+            get(handlers::get_game)
+                .put(handlers::update_game)
+                .patch(handlers::update_game)
+                .delete(handlers::delete_game),
+        )
+        .route("/api/admin/games/{id}/availability", put(handlers::set_game_availability))
         .route("/api/admin/games/{id}/metadata", post(handlers::fetch_game_metadata))
+        .route("/api/admin/games/{id}/manifest", post(handlers::regenerate_manifest))
+        .route("/api/admin/games/{id}/media", post(handlers::mirror_game_media))
+        .route("/api/admin/games/{id}/builds", get(handlers::list_game_builds).post(handlers::create_game_build))
+        .route("/api/admin/games/{id}/builds/{build_id}/current", post(handlers::set_current_build))
         .route("/api/admin/search/igdb", get(handlers::search_igdb_games))
-        .route_layer(from_fn_with_state(state.clone(), middleware::admin_middleware))
+        .route("/api/admin/cache/igdb", delete(handlers::purge_igdb_cache))
+        .route("/api/admin/search/metadata", get(handlers::search_metadata))
+        .route("/api/admin/metadata/providers", get(handlers::list_metadata_providers))
+        .route("/api/admin/metadata/match", post(handlers::run_metadata_matching))
+        .route("/api/admin/metadata/refresh", post(handlers::refresh_all_metadata))
+        .route("/api/admin/metadata/matches", get(handlers::list_metadata_matches))
+        .route("/api/admin/metadata/matches/{id}/accept", post(handlers::accept_metadata_match))
+        .route("/api/admin/metadata/matches/{id}/reject", post(handlers::reject_metadata_match))
+        .route("/api/admin/library/scan", post(handlers::scan_library))
+        .route("/api/admin/jobs", get(job_handlers::list_jobs))
+        .route("/api/admin/jobs/{id}", get(job_handlers::get_job))
+        .route("/api/admin/jobs/{id}/cancel", post(job_handlers::cancel_job))
+        .route("/api/admin/schedule", get(job_handlers::list_scheduled_tasks))
+        .route("/api/admin/catalog/export", get(handlers::export_catalog))
+        .route("/api/admin/catalog/import", post(handlers::import_catalog))
+        .route_layer(from_fn_with_state(state.clone(), middleware::admin_middleware));
 
-        // Health check (no auth)
-        .route("/health", get(handlers::health_check))
+    // Mirrored artwork; file names are derived from the source URL, so they never change in place
+    let media_routes = Router::new()
+        .nest_service(media::MEDIA_URL_PREFIX, ServeDir::new(state.media.dir()))
+        .layer(SetResponseHeaderLayer::overriding(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("public, max-age=31536000, immutable"),
+        ));
+
+    // Build the application router with multi-user game management.
+    // Each group carries its own route_layer so auth only wraps the routes it is meant for.
+    let app = Router::new()
+        .merge(public_routes)
+        .merge(user_routes)
+        .merge(admin_routes)
+        .merge(media_routes)
         .with_state(state)
         .layer(CorsLayer::permissive())
         .fallback_service(ServeDir::new("static"));
@@ -99,12 +437,16 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("Admin Web interface available at http://localhost:{}", port);
     tracing::info!("API Endpoints:");
     tracing::info!("  - POST /api/auth/login - User login");
+    tracing::info!("  - GET /api/auth/oidc/login - Sign in with the OpenID Connect provider");
     tracing::info!("  - GET /api/store/games - Browse available games");
     tracing::info!("  - GET /api/user/library - User's personal library");
     tracing::info!("  - POST /api/user/games/{{id}}/install - Install game");
+    tracing::info!("  - GET /api/user/games/{{id}}/download - Download a game's installer (resumable)");
+    tracing::info!("  - GET /api/user/games/{{id}}/files/{{path}} - Download one file of a folder game (resumable)");
     tracing::info!("  - Admin routes under /api/admin/*");
 
-    axum::serve(listener, app).await?;
+    // Peer addresses are recorded on login sessions
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>()).await?;
 
     Ok(())
 }
@@ -0,0 +1,199 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use uuid::Uuid;
+use crate::{
+    handlers::AppState,
+    jobs::{self, JobContext, JobPayload},
+    models::{GameFile, Job},
+};
+
+// Hash a game's files in a background job; the manifest reads as pending until it finishes
+pub async fn queue_generation(state: &AppState, game_id: String) -> Option<Job> {
+    if let Err(e) = state.db.set_manifest_status(&game_id, "pending", None).await {
+        tracing::error!("Failed to mark manifest pending for {}: {}", game_id, e);
+    }
+
+    match jobs::enqueue(state, JobPayload::GenerateManifest { game_id: game_id.clone() }, None).await {
+        Ok(job) => Some(job),
+        Err(e) => {
+            tracing::error!("Failed to queue manifest generation for {}: {}", game_id, e);
+            None
+        }
+    }
+}
+
+pub async fn generate(state: &AppState, game_id: &str, context: &JobContext) -> Result<usize> {
+    let game = state
+        .db
+        .get_game_by_id(game_id)
+        .await?
+        .ok_or_else(|| anyhow!("game not found"))?;
+    let file_path = game.file_path.ok_or_else(|| anyhow!("game has no file_path"))?;
+
+    // Files whose size and mtime are unchanged keep their previous hash instead of being re-read
+    let previous: HashMap<String, GameFile> = state
+        .db
+        .get_manifest_files(game_id)
+        .await?
+        .into_iter()
+        .map(|file| (file.relative_path.clone(), file))
+        .collect();
+
+    let game_id = game_id.to_string();
+    let (files, fingerprint) = {
+        let game_id = game_id.clone();
+        let context = context.clone();
+        tokio::task::spawn_blocking(move || build_file_list(&game_id, Path::new(&file_path), &previous, &context))
+            .await??
+    };
+
+    state.db.replace_manifest(&game_id, &files, &fingerprint).await?;
+
+    Ok(files.len())
+}
+
+// Digest of every file's path, size and mtime. The scanner compares it with the manifest's
+// to notice files replaced in place, which a size check alone misses.
+pub fn fingerprint(root: &Path) -> Result<String> {
+    Ok(fingerprint_of(&list_files(root)?))
+}
+
+fn fingerprint_of(entries: &[(String, fs::Metadata, PathBuf)]) -> String {
+    let mut hasher = Sha256::new();
+    for (relative_path, metadata, _) in entries {
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|since_epoch| since_epoch.as_nanos())
+            .unwrap_or(0);
+        hasher.update(format!("{}\0{}\0{}\n", relative_path, metadata.len(), modified));
+    }
+    hex::encode(hasher.finalize())
+}
+
+// Every file of a game by its "/"-separated path relative to the game, sorted by that path
+fn list_files(root: &Path) -> Result<Vec<(String, fs::Metadata, PathBuf)>> {
+    let metadata = fs::metadata(root)?;
+
+    let entries: Vec<(String, PathBuf)> = if metadata.is_dir() {
+        let mut paths = Vec::new();
+        collect_files(root, &mut paths)?;
+        paths
+            .into_iter()
+            .filter_map(|path| {
+                let relative = path.strip_prefix(root).ok()?;
+                let relative = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                Some((relative, path))
+            })
+            .collect()
+    } else {
+        let name = root
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| anyhow!("invalid file path"))?;
+        vec![(name, root.to_path_buf())]
+    };
+
+    let mut entries = entries
+        .into_iter()
+        .map(|(relative_path, path)| Ok((relative_path, fs::metadata(&path)?, path)))
+        .collect::<Result<Vec<_>>>()?;
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(entries)
+}
+
+fn build_file_list(
+    game_id: &str,
+    root: &Path,
+    previous: &HashMap<String, GameFile>,
+    context: &JobContext,
+) -> Result<(Vec<GameFile>, String)> {
+    // Taken before hashing, so a file changed meanwhile shows up as changed on the next scan
+    let entries = list_files(root)?;
+    let fingerprint = fingerprint_of(&entries);
+
+    // Progress is measured in bytes, counting reused hashes as done
+    let total_bytes: u64 = entries.iter().map(|(_, metadata, _)| metadata.len()).sum::<u64>().max(1);
+    let mut done_bytes = 0u64;
+    let file_count = entries.len();
+
+    let mut files = Vec::with_capacity(file_count);
+    for (index, (relative_path, metadata, path)) in entries.into_iter().enumerate() {
+        context.check_cancelled()?;
+
+        let size = metadata.len() as i64;
+        let modified_at: Option<DateTime<Utc>> = metadata.modified().ok().map(DateTime::from);
+
+        let sha256 = match previous.get(&relative_path) {
+            Some(prev) if prev.size == size && prev.modified_at.is_some() && prev.modified_at == modified_at => {
+                prev.sha256.clone()
+            }
+            _ => hash_file(&path, context, |hashed| {
+                let percent = (done_bytes + hashed) as f64 / total_bytes as f64 * 100.0;
+                context.set_progress(percent, Some(format!("Hashing file {} of {}", index + 1, file_count)));
+            })?,
+        };
+        done_bytes += metadata.len();
+        context.set_progress(done_bytes as f64 / total_bytes as f64 * 100.0, None);
+
+        files.push(GameFile {
+            id: Uuid::new_v4().to_string(),
+            game_id: game_id.to_string(),
+            relative_path,
+            size,
+            sha256,
+            modified_at,
+        });
+    }
+
+    Ok((files, fingerprint))
+}
+
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), out)?;
+        } else if file_type.is_file() {
+            out.push(entry.path());
+        }
+    }
+
+    Ok(())
+}
+
+fn hash_file(path: &Path, context: &JobContext, mut on_progress: impl FnMut(u64)) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    let mut hashed = 0u64;
+
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+
+        // Large installers take a while, so report and honour cancellation per chunk
+        hashed += read as u64;
+        on_progress(hashed);
+        context.check_cancelled()?;
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
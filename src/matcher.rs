@@ -0,0 +1,278 @@
+use anyhow::{Result, anyhow};
+use chrono::Datelike;
+use serde::Serialize;
+use std::collections::HashSet;
+use crate::{
+    handlers::AppState,
+    jobs::{self, JobPayload},
+    media,
+    models::{Game, GameMetadata, Job},
+};
+
+// A candidate at least this good, and clearly ahead of the runner-up, is linked without review
+const AUTO_LINK_SCORE: f64 = 0.9;
+const AUTO_LINK_MARGIN: f64 = 0.05;
+
+// Candidates below this are not worth an admin's time
+const REVIEW_SCORE: f64 = 0.5;
+const MAX_REVIEW_CANDIDATES: usize = 5;
+const SEARCH_LIMIT: u32 = 10;
+
+// Trailing "<word> edition" suffixes that name a release of the same game
+const EDITION_WORDS: &[&str] = &[
+    "goty", "deluxe", "definitive", "complete", "ultimate", "gold", "special", "collectors",
+    "enhanced", "anniversary", "premium", "standard", "digital", "legendary",
+];
+
+// Tokens that introduce a version number, e.g. "build 1234", "update 5"
+const VERSION_WORDS: &[&str] = &["build", "update", "patch", "rev"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchOutcome {
+    AlreadyLinked,
+    Linked,
+    Queued(usize),
+    NoMatch,
+    // Outcomes of refresh_game for games that were already linked
+    Refreshed,
+    NotFound,
+}
+
+// Lowercased title with bracketed tags, version numbers, edition suffixes and punctuation removed
+pub fn normalize_title(title: &str) -> String {
+    let mut unbracketed = String::with_capacity(title.len());
+    let mut depth = 0usize;
+    for c in title.to_lowercase().chars() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => unbracketed.push(c),
+            _ => {}
+        }
+    }
+
+    // Dots survive this pass so version numbers like v1.2.3 stay one token
+    let spaced: String = unbracketed
+        .chars()
+        .filter(|c| *c != '\'' && *c != '’')
+        .map(|c| if c.is_alphanumeric() || c == '.' { c } else { ' ' })
+        .collect();
+
+    let mut tokens: Vec<String> = Vec::new();
+    let mut skip_number = false;
+    for token in spaced.split_whitespace() {
+        if skip_number && token.chars().next().is_some_and(|c| c.is_ascii_digit()) {
+            skip_number = false;
+            continue;
+        }
+        skip_number = VERSION_WORDS.contains(&token);
+        if skip_number || is_version(token) {
+            continue;
+        }
+
+        let token: String = token.chars().filter(|c| *c != '.').collect();
+        if !token.is_empty() {
+            tokens.push(token);
+        }
+    }
+
+    strip_edition(&mut tokens);
+    if tokens.len() > 1 && tokens.last().is_some_and(|token| title_year(token).is_some() && token.len() == 4) {
+        tokens.pop();
+    }
+    if tokens.len() > 1 && tokens[0] == "the" {
+        tokens.remove(0);
+    }
+
+    tokens.join(" ")
+}
+
+fn is_version(token: &str) -> bool {
+    let digits = token.strip_prefix('v').unwrap_or(token);
+    let numeric = !digits.is_empty()
+        && digits.starts_with(|c: char| c.is_ascii_digit())
+        && digits.chars().all(|c| c.is_ascii_digit() || c == '.');
+    // A bare "2" is a sequel number, "1.2" or "v2" is a version
+    numeric && (digits.contains('.') || token.starts_with('v'))
+}
+
+fn strip_edition(tokens: &mut Vec<String>) {
+    loop {
+        let len = tokens.len();
+        if len >= 5 && tokens[len - 5..].join(" ") == "game of the year edition" {
+            tokens.truncate(len - 5);
+        } else if len >= 3 && tokens[len - 1] == "edition" && EDITION_WORDS.contains(&tokens[len - 2].as_str()) {
+            tokens.truncate(len - 2);
+        } else if len >= 2 && tokens[len - 1] == "goty" {
+            tokens.truncate(len - 1);
+        } else if len >= 3 && tokens[len - 2..].join(" ") == "directors cut" {
+            tokens.truncate(len - 2);
+        } else {
+            return;
+        }
+    }
+}
+
+// A plausible release year written in the title, e.g. "Doom (2016)"
+pub fn title_year(title: &str) -> Option<i32> {
+    title
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|part| part.len() == 4)
+        .filter_map(|part| part.parse::<i32>().ok())
+        .find(|year| (1970..=2100).contains(year))
+}
+
+// 1.0 for identical strings down to 0.0, based on edit distance
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    1.0 - previous[b.len()] as f64 / longest as f64
+}
+
+pub fn score(title: &str, year: Option<i32>, candidate: &GameMetadata) -> f64 {
+    let title = normalize_title(title);
+    let candidate_name = normalize_title(&candidate.name);
+    let mut score = similarity(&title, &candidate_name);
+
+    // "Witcher 3" vs "The Witcher 3: Wild Hunt": every word matches, so it deserves a look
+    // even though the edit distance is large; never enough to link on its own
+    let title_words: HashSet<&str> = title.split(' ').collect();
+    let candidate_words: HashSet<&str> = candidate_name.split(' ').collect();
+    if !title.is_empty() && (title_words.is_subset(&candidate_words) || candidate_words.is_subset(&title_words)) {
+        score = score.max(0.7);
+    }
+
+    if let (Some(year), Some(candidate_year)) = (year, candidate.release_date.map(|date| date.year())) {
+        match (year - candidate_year).abs() {
+            0 => score += 0.05,
+            1 => {}
+            _ => score -= 0.15,
+        }
+    }
+
+    score.clamp(0.0, 1.0)
+}
+
+// Run the matcher in a background job; games that already have a provider link are left alone
+pub async fn queue_match(state: &AppState, game_id: String) -> Option<Job> {
+    match jobs::enqueue(state, JobPayload::MatchMetadata { game_id: game_id.clone() }, None).await {
+        Ok(job) => Some(job),
+        Err(e) => {
+            tracing::error!("Failed to queue metadata matching for {}: {}", game_id, e);
+            None
+        }
+    }
+}
+
+// The game's id at `provider`. An id recorded for a different provider than the one now
+// chosen does not count; games linked before providers existed only carry an igdb_id.
+fn linked_id(game: &Game, provider: &str) -> Option<String> {
+    match (&game.metadata_id, game.igdb_id) {
+        (Some(metadata_id), _) if game.metadata_provider.as_deref() == Some(provider) => Some(metadata_id.clone()),
+        (_, Some(igdb_id)) if provider == "igdb" => Some(igdb_id.to_string()),
+        _ => None,
+    }
+}
+
+// Re-fetch metadata from the game's provider, or run the matcher if it is not linked there yet
+pub async fn refresh_game(state: &AppState, game_id: &str) -> Result<MatchOutcome> {
+    let game = state
+        .db
+        .get_game_by_id(game_id)
+        .await?
+        .ok_or_else(|| anyhow!("game not found"))?;
+
+    let provider = state.metadata_providers.for_game(game.metadata_provider.as_deref());
+    let external_id = match linked_id(&game, provider.id()) {
+        Some(external_id) => external_id,
+        None => return match_game(state, game_id).await,
+    };
+
+    match provider.fetch(&external_id).await? {
+        Some(metadata) => {
+            state.db.update_game_metadata(game_id, &metadata).await?;
+            media::queue_mirror(state, game_id.to_string()).await;
+            Ok(MatchOutcome::Refreshed)
+        }
+        None => {
+            tracing::warn!("Game not found in {}: {}", provider.display_name(), external_id);
+            Ok(MatchOutcome::NotFound)
+        }
+    }
+}
+
+pub async fn match_game(state: &AppState, game_id: &str) -> Result<MatchOutcome> {
+    let game = state
+        .db
+        .get_game_by_id(game_id)
+        .await?
+        .ok_or_else(|| anyhow!("game not found"))?;
+
+    let provider = state.metadata_providers.for_game(game.metadata_provider.as_deref());
+    if linked_id(&game, provider.id()).is_some() {
+        return Ok(MatchOutcome::AlreadyLinked);
+    }
+    if !provider.capabilities().search {
+        return Err(anyhow!("{} does not support search", provider.display_name()));
+    }
+
+    let query = normalize_title(&game.name);
+    if query.is_empty() {
+        return Ok(MatchOutcome::NoMatch);
+    }
+    let year = game.release_date.map(|date| date.year()).or_else(|| title_year(&game.name));
+
+    let mut candidates: Vec<(GameMetadata, f64)> = provider
+        .search(&query, SEARCH_LIMIT)
+        .await?
+        .into_iter()
+        .map(|candidate| {
+            let score = score(&game.name, year, &candidate);
+            (candidate, score)
+        })
+        .filter(|(_, score)| *score >= REVIEW_SCORE)
+        .collect();
+    candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+    candidates.truncate(MAX_REVIEW_CANDIDATES);
+
+    let (best, best_score) = match candidates.first() {
+        Some((best, score)) => (best.clone(), *score),
+        None => {
+            tracing::info!("No metadata match for game {} ({})", game.name, query);
+            return Ok(MatchOutcome::NoMatch);
+        }
+    };
+    let runner_up = candidates.get(1).map(|(_, score)| *score).unwrap_or(0.0);
+
+    if best_score >= AUTO_LINK_SCORE && best_score - runner_up >= AUTO_LINK_MARGIN {
+        state.db.update_game_metadata(game_id, &best).await?;
+        state.db.record_match_candidates(game_id, &candidates[..1], "auto").await?;
+        media::queue_mirror(state, game_id.to_string()).await;
+        tracing::info!(
+            "Linked game {} to {} {} ({}, score {:.2})",
+            game.name, provider.display_name(), best.external_id, best.name, best_score
+        );
+        return Ok(MatchOutcome::Linked);
+    }
+
+    let queued = state.db.record_match_candidates(game_id, &candidates, "pending").await?;
+    tracing::info!("Queued {} metadata candidate(s) for review for game {}", queued, game.name);
+    Ok(MatchOutcome::Queued(queued))
+}
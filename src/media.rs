@@ -0,0 +1,326 @@
+use anyhow::{Result, anyhow};
+use image::{ImageFormat, imageops::FilterType};
+use reqwest::Client;
+use sha2::{Digest, Sha256};
+use std::{
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use crate::{
+    handlers::AppState,
+    jobs::{self, JobContext, JobPayload},
+    models::Job,
+};
+
+// Public prefix the media directory is served under
+pub const MEDIA_URL_PREFIX: &str = "/media";
+
+// Thumbnails are written next to the original as <name>_<size>.jpg, bounded to these widths
+pub const THUMBNAIL_SIZES: &[(&str, u32)] = &[("small", 160), ("medium", 320), ("large", 640)];
+
+// Refuse anything bigger than this; artwork is a few MB at most
+const MAX_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// Between reads, so a slow but steady download still finishes; the whole fetch is capped too
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+const FETCH_TIMEOUT: Duration = Duration::from_secs(120);
+const MAX_REDIRECTS: usize = 5;
+
+pub struct MediaMirror {
+    dir: PathBuf,
+    enabled: bool,
+}
+
+impl MediaMirror {
+    pub fn new(dir: PathBuf, enabled: bool) -> Self {
+        Self {
+            dir,
+            enabled,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // Download one remote image (if not already mirrored) plus its thumbnails and return the local URL
+    async fn mirror_url(&self, game_id: &str, url: &str) -> Result<String> {
+        let name = hex::encode(&Sha256::digest(url.as_bytes())[..8]);
+        let game_dir = self.dir.join("games").join(game_id);
+
+        // Content is keyed by source URL, so an existing original means the work is done
+        for format in [ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::WebP] {
+            let extension = format.extensions_str()[0];
+            if game_dir.join(format!("{}.{}", name, extension)).exists() {
+                return Ok(local_url(game_id, &name, extension));
+            }
+        }
+
+        let bytes = fetch_image(url).await?;
+
+        let game_id = game_id.to_string();
+        tokio::task::spawn_blocking(move || {
+            let format = image::guess_format(&bytes)?;
+            let extension = match format {
+                ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP => format.extensions_str()[0],
+                other => return Err(anyhow!("unsupported image format {:?}", other)),
+            };
+            let image = image::load_from_memory_with_format(&bytes, format)?;
+
+            std::fs::create_dir_all(&game_dir)?;
+
+            // Thumbnails first: the original appearing is what marks the image as mirrored
+            for (size, width) in THUMBNAIL_SIZES {
+                let thumbnail = if image.width() > *width {
+                    image.resize(*width, u32::MAX, FilterType::Lanczos3)
+                } else {
+                    image.clone()
+                };
+                thumbnail
+                    .to_rgb8()
+                    .save_with_format(game_dir.join(format!("{}_{}.jpg", name, size)), ImageFormat::Jpeg)?;
+            }
+
+            let original = game_dir.join(format!("{}.{}", name, extension));
+            let partial = game_dir.join(format!("{}.{}.part", name, extension));
+            std::fs::write(&partial, &bytes)?;
+            std::fs::rename(&partial, &original)?;
+
+            Ok(local_url(&game_id, &name, extension))
+        })
+        .await?
+    }
+
+    pub async fn remove_game(&self, game_id: &str) -> Result<()> {
+        match tokio::fs::remove_dir_all(self.dir.join("games").join(game_id)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Artwork URLs come from metadata providers and admins, so only public hosts are fetched.
+// Each hop is resolved and checked here, then connected to at exactly the checked addresses,
+// so neither redirects nor DNS rebinding can reach the server's own network.
+async fn fetch_image(url: &str) -> Result<Vec<u8>> {
+    let mut url = reqwest::Url::parse(url)?;
+
+    for _ in 0..=MAX_REDIRECTS {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(anyhow!("refusing to fetch {}: unsupported scheme", url));
+        }
+        let host = url.host_str().ok_or_else(|| anyhow!("refusing to fetch {}: no host", url))?.to_string();
+        let port = url.port_or_known_default().unwrap_or(80);
+
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.trim_start_matches('[').trim_end_matches(']'), port))
+            .await?
+            .collect();
+        if addrs.is_empty() || addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+            return Err(anyhow!("refusing to fetch {}: host is not a public address", url));
+        }
+
+        let client = Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .connect_timeout(CONNECT_TIMEOUT)
+            .read_timeout(READ_TIMEOUT)
+            .timeout(FETCH_TIMEOUT)
+            .resolve_to_addrs(&host, &addrs)
+            .build()?;
+        let mut response = client.get(url.clone()).send().await?;
+
+        if response.status().is_redirection() {
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|value| value.to_str().ok())
+                .ok_or_else(|| anyhow!("redirect from {} has no location", url))?;
+            url = url.join(location)?;
+            continue;
+        }
+        if !response.status().is_success() {
+            return Err(anyhow!("download of {} failed: {}", url, response.status()));
+        }
+        if response.content_length().is_some_and(|length| length > MAX_IMAGE_BYTES as u64) {
+            return Err(anyhow!("image {} is too large ({} bytes)", url, response.content_length().unwrap_or_default()));
+        }
+
+        // Content-Length can be missing or wrong, so the cap also applies while reading
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if bytes.len() + chunk.len() > MAX_IMAGE_BYTES {
+                return Err(anyhow!("image {} is larger than {} bytes", url, MAX_IMAGE_BYTES));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        return Ok(bytes);
+    }
+
+    Err(anyhow!("too many redirects fetching {}", url))
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local fc00::/7 and link-local fe80::/10
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+fn local_url(game_id: &str, name: &str, extension: &str) -> String {
+    format!("{}/games/{}/{}.{}", MEDIA_URL_PREFIX, game_id, name, extension)
+}
+
+fn is_remote(url: &str) -> bool {
+    url.starts_with("https://") || url.starts_with("http://")
+}
+
+// Copy a game's cover and screenshots into the media directory in a background job and point
+// the stored URLs at the local copies. Images that fail to download keep their remote URL.
+pub async fn queue_mirror(state: &AppState, game_id: String) -> Option<Job> {
+    if !state.media.enabled {
+        return None;
+    }
+
+    match jobs::enqueue(state, JobPayload::MirrorMedia { game_id: game_id.clone() }, None).await {
+        Ok(job) => Some(job),
+        Err(e) => {
+            tracing::error!("Failed to queue media mirroring for {}: {}", game_id, e);
+            None
+        }
+    }
+}
+
+pub async fn mirror_game(state: &AppState, game_id: &str, context: &JobContext) -> Result<usize> {
+    let game = state
+        .db
+        .get_game_by_id(game_id)
+        .await?
+        .ok_or_else(|| anyhow!("game not found"))?;
+
+    let mut mirrored = 0;
+    let screenshot_count = game
+        .screenshots
+        .as_deref()
+        .and_then(|raw| serde_json::from_str::<Vec<String>>(raw).ok())
+        .map_or(0, |urls| urls.len());
+    let total = (1 + screenshot_count) as f64;
+    context.set_progress(0.0, Some("Mirroring cover".to_string()));
+
+    let cover_url = match game.cover_url.clone() {
+        Some(url) if is_remote(&url) => match state.media.mirror_url(game_id, &url).await {
+            Ok(local) => {
+                mirrored += 1;
+                Some(local)
+            }
+            Err(e) => {
+                tracing::warn!("Failed to mirror cover for game {}: {}", game_id, e);
+                Some(url)
+            }
+        },
+        other => other,
+    };
+
+    let screenshots: Option<Vec<String>> = game
+        .screenshots
+        .as_deref()
+        .and_then(|raw| serde_json::from_str(raw).ok());
+    let screenshots = match screenshots {
+        Some(urls) => {
+            let mut local_urls = Vec::with_capacity(urls.len());
+            for (index, url) in urls.into_iter().enumerate() {
+                context.check_cancelled()?;
+                context.set_progress(
+                    (1 + index) as f64 / total * 100.0,
+                    Some(format!("Mirroring screenshot {} of {}", index + 1, screenshot_count)),
+                );
+                if !is_remote(&url) {
+                    local_urls.push(url);
+                    continue;
+                }
+                match state.media.mirror_url(game_id, &url).await {
+                    Ok(local) => {
+                        mirrored += 1;
+                        local_urls.push(local);
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to mirror screenshot for game {}: {}", game_id, e);
+                        local_urls.push(url);
+                    }
+                }
+            }
+            Some(serde_json::to_string(&local_urls)?)
+        }
+        None => game.screenshots.clone(),
+    };
+
+    if mirrored > 0 {
+        let (old_cover_url, old_screenshots) = (game.cover_url.as_deref(), game.screenshots.as_deref());
+        if !state.db.set_game_media(game_id, (old_cover_url, old_screenshots), cover_url, screenshots).await? {
+            tracing::info!("Game {} artwork changed while mirroring; leaving it alone", game_id);
+            return Ok(0);
+        }
+    }
+
+    Ok(mirrored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn public(ip: &str) -> bool {
+        is_public_ip(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn allows_public_addresses() {
+        for ip in ["8.8.8.8", "151.101.1.1", "100.128.0.1", "2606:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(public(ip), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn refuses_internal_addresses() {
+        for ip in [
+            "10.0.0.1", "172.16.5.4", "192.168.1.1", "127.0.0.1", "169.254.169.254", "0.0.0.0",
+            "0.1.2.3", "255.255.255.255", "192.0.2.1", "224.0.0.1", "100.64.0.1", "100.127.255.255",
+        ] {
+            assert!(!public(ip), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn refuses_internal_ipv6_addresses() {
+        for ip in ["::1", "::", "ff02::1", "fc00::1", "fd12:3456::1", "fe80::1", "::ffff:127.0.0.1", "::ffff:10.0.0.1"] {
+            assert!(!public(ip), "{}", ip);
+        }
+    }
+}
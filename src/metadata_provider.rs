@@ -0,0 +1,72 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use std::sync::Arc;
+use crate::models::GameMetadata;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ProviderCapabilities {
+    pub search: bool,
+    pub fetch_by_id: bool,
+    pub screenshots: bool,
+    pub storyline: bool,
+    pub ratings: bool,
+}
+
+#[async_trait]
+pub trait MetadataProvider: Send + Sync {
+    // Stable identifier stored in games.metadata_provider, e.g. "igdb"
+    fn id(&self) -> &'static str;
+    fn display_name(&self) -> &'static str;
+    fn capabilities(&self) -> ProviderCapabilities;
+    async fn search(&self, query: &str, limit: u32) -> Result<Vec<GameMetadata>>;
+    async fn fetch(&self, external_id: &str) -> Result<Option<GameMetadata>>;
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProviderInfo {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub capabilities: ProviderCapabilities,
+    pub is_default: bool,
+}
+
+pub struct MetadataProviders {
+    providers: Vec<Arc<dyn MetadataProvider>>,
+    default_id: String,
+}
+
+impl MetadataProviders {
+    pub fn new(providers: Vec<Arc<dyn MetadataProvider>>, default_id: String) -> Self {
+        Self { providers, default_id }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<dyn MetadataProvider>> {
+        self.providers.iter().find(|p| p.id() == id).cloned()
+    }
+
+    // Falls back to the first registered provider if the configured default is unknown
+    pub fn default_provider(&self) -> Arc<dyn MetadataProvider> {
+        self.get(&self.default_id).unwrap_or_else(|| self.providers[0].clone())
+    }
+
+    // The provider a game should use: its own choice if set and known, otherwise the default
+    pub fn for_game(&self, provider_id: Option<&str>) -> Arc<dyn MetadataProvider> {
+        provider_id
+            .and_then(|id| self.get(id))
+            .unwrap_or_else(|| self.default_provider())
+    }
+
+    pub fn list(&self) -> Vec<ProviderInfo> {
+        let default_id = self.default_provider().id();
+        self.providers
+            .iter()
+            .map(|p| ProviderInfo {
+                id: p.id(),
+                name: p.display_name(),
+                capabilities: p.capabilities(),
+                is_default: p.id() == default_id,
+            })
+            .collect()
+    }
+}
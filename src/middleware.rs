@@ -1,33 +1,99 @@
 use axum::{
     extract::{Request, State},
-    http::StatusCode,
+    http::{Method, StatusCode},
     middleware::Next,
     response::Response,
 };
-use crate::{handlers::AppState};
+use crate::{
+    auth::{User, ACCESS_TOKEN_PREFIX},
+    handlers::AppState,
+};
 
-pub async fn auth_middleware(
-    State(state): State<AppState>,
-    mut request: Request,
-    next: Next,
-) -> Result<Response, StatusCode> {
-    let auth_header = request
+// All a user with an admin-issued temporary password may do until they pick a new one
+const PASSWORD_CHANGE_PATHS: &[&str] = &["/api/auth/password", "/api/auth/me", "/api/auth/logout"];
+
+// The scope a personal access token needs for a route. Routes that map to None (sessions,
+// password, token management) are only reachable with a password login, so a leaked token
+// cannot be used to mint new credentials or lock the owner out.
+fn required_scope(method: &Method, path: &str) -> Option<&'static str> {
+    if path == "/api/auth/me" {
+        return Some("");
+    }
+    if ["/api/admin/users", "/api/admin/roles", "/api/admin/groups", "/api/admin/access-rules"]
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+    {
+        return Some("admin:users");
+    }
+    if path.starts_with("/api/admin/") {
+        return Some("admin:games");
+    }
+    if let Some(rest) = path.strip_prefix("/api/user/games/") {
+        // What follows the game id: download, files/..., install and so on
+        return match rest.split('/').nth(1) {
+            Some("download" | "files") => Some("downloads"),
+            Some("install" | "uninstall") => Some("library:write"),
+            _ if method == Method::GET => Some("library:read"),
+            _ => None,
+        };
+    }
+    if (path.starts_with("/api/store/") || path.starts_with("/api/user/library")) && method == Method::GET {
+        return Some("library:read");
+    }
+    None
+}
+
+// Resolve the bearer token to a user. Session tokens grant everything the user may do;
+// personal access tokens only what their scopes allow.
+async fn authenticate(state: &AppState, request: &mut Request) -> Result<User, StatusCode> {
+    let token = request
         .headers()
         .get("Authorization")
         .and_then(|header| header.to_str().ok())
-        .and_then(|header| header.strip_prefix("Bearer "));
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .ok_or(StatusCode::UNAUTHORIZED)?
+        .to_string();
 
-    let token = match auth_header {
-        Some(token) => token,
-        None => return Err(StatusCode::UNAUTHORIZED),
-    };
+    let user = if token.starts_with(ACCESS_TOKEN_PREFIX) {
+        let (user, access_token) = state
+            .auth_service
+            .validate_access_token(&token)
+            .await
+            .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+        let allowed = match required_scope(request.method(), request.uri().path()) {
+            Some("") => true,
+            Some(scope) => access_token.has_scope(scope),
+            None => false,
+        };
+        if !allowed || user.must_change_password {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        user
+    } else {
+        let (user, session) = state
+            .auth_service
+            .validate_session(&token)
+            .await
+            .map_err(|_| StatusCode::UNAUTHORIZED)?;
 
-    let user = match state.auth_service.validate_session(token).await {
-        Ok(user) => user,
-        Err(_) => return Err(StatusCode::UNAUTHORIZED),
+        if user.must_change_password && !PASSWORD_CHANGE_PATHS.contains(&request.uri().path()) {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        request.extensions_mut().insert(session);
+        user
     };
 
-    request.extensions_mut().insert(user);
+    request.extensions_mut().insert(user.clone());
+    Ok(user)
+}
+
+pub async fn auth_middleware(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    authenticate(&state, &mut request).await?;
 
     Ok(next.run(request).await)
 }
@@ -37,27 +103,19 @@ pub async fn admin_middleware(
     mut request: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
-    let auth_header = request
-        .headers()
-        .get("Authorization")
-        .and_then(|header| header.to_str().ok())
-        .and_then(|header| header.strip_prefix("Bearer "));
-
-    let token = match auth_header {
-        Some(token) => token,
-        None => return Err(StatusCode::UNAUTHORIZED),
-    };
+    let user = authenticate(&state, &mut request).await?;
 
-    let user = match state.auth_service.validate_session(token).await {
-        Ok(user) => user,
-        Err(_) => return Err(StatusCode::UNAUTHORIZED),
-    };
-
-    if !user.is_admin {
+    // Any role that grants something lets the user in; handlers check the specific
+    // permission with RequirePermission
+    let permissions = state
+        .auth_service
+        .get_permissions(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if permissions.is_empty() {
         return Err(StatusCode::FORBIDDEN);
     }
-
-    request.extensions_mut().insert(user);
+    request.extensions_mut().insert(permissions);
 
     Ok(next.run(request).await)
 }
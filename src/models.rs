@@ -1,4 +1,4 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::FromRow;
 use chrono::{DateTime, Utc};
 
@@ -19,9 +19,17 @@ pub struct Game {
     pub publisher: Option<String>,
     pub file_path: Option<String>,
     pub file_size: Option<i64>,
-    pub is_installed: bool,
+    pub is_available: bool,
+    pub added_by: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub current_build_id: Option<String>,
+    pub metadata_overrides: Option<String>, // JSON array as string
+    pub metadata_provider: Option<String>,
+    pub metadata_id: Option<String>,
+    pub metadata_updated_at: Option<DateTime<Utc>>,
+    // Hidden by the library scanner because its files are gone
+    pub missing_on_disk: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -29,21 +37,386 @@ pub struct CreateGameRequest {
     pub name: String,
     pub igdb_id: Option<i64>,
     pub file_path: Option<String>,
+    pub file_size: Option<i64>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// Metadata columns an admin can override; a refresh from the metadata provider skips overridden ones
+pub const METADATA_FIELDS: &[&str] = &[
+    "summary", "storyline", "rating", "release_date", "cover_url", "screenshots",
+    "genres", "platforms", "developer", "publisher",
+];
+
+// Partial update: a missing field is left alone, an explicit null clears it
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct UpdateGameRequest {
     pub name: Option<String>,
-    pub file_path: Option<String>,
-    pub is_installed: Option<bool>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub igdb_id: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub summary: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub storyline: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub rating: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub release_date: Option<Option<DateTime<Utc>>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub cover_url: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub screenshots: Option<Option<Vec<String>>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub genres: Option<Option<Vec<String>>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub platforms: Option<Option<Vec<String>>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub developer: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub publisher: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub file_path: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub file_size: Option<Option<i64>>,
+    pub is_available: Option<bool>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub metadata_provider: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub metadata_id: Option<Option<String>>,
+    // Replaces the override list outright, e.g. [] hands every field back to the metadata provider
+    pub metadata_overrides: Option<Vec<String>>,
+}
+
+fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetAvailabilityRequest {
+    pub is_available: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteGameResponse {
+    pub id: String,
+    pub affected_libraries: i64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct GameFilter {
+    pub q: Option<String>,
+    pub genre: Option<String>,
+    pub platform: Option<String>,
+    pub developer: Option<String>,
+    pub publisher: Option<String>,
+    pub year_from: Option<i32>,
+    pub year_to: Option<i32>,
+    pub min_rating: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GameFacets {
+    pub genres: Vec<FacetCount>,
+    pub platforms: Vec<FacetCount>,
+    pub developers: Vec<FacetCount>,
+    pub publishers: Vec<FacetCount>,
+    pub release_years: Vec<FacetCount>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct GameListItem {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub game: Game,
+    // Only set when the list comes from a full-text search
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search_rank: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GameListResponse {
-    pub games: Vec<Game>,
+    pub games: Vec<GameListItem>,
     pub total: i64,
     pub page: i64,
     pub per_page: i64,
+    pub facets: GameFacets,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct GameBuild {
+    pub id: String,
+    pub game_id: String,
+    pub version: String,
+    pub release_notes: Option<String>,
+    pub file_path: String,
+    pub file_size: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+// A build as users see it; where it lives on the server is not their business
+#[derive(Debug, Serialize)]
+pub struct GameBuildResponse {
+    pub id: String,
+    pub game_id: String,
+    pub version: String,
+    pub release_notes: Option<String>,
+    pub file_size: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<GameBuild> for GameBuildResponse {
+    fn from(build: GameBuild) -> Self {
+        Self {
+            id: build.id,
+            game_id: build.game_id,
+            version: build.version,
+            release_notes: build.release_notes,
+            file_size: build.file_size,
+            created_at: build.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateBuildRequest {
+    pub version: String,
+    pub release_notes: Option<String>,
+    pub file_path: String,
+    pub file_size: Option<i64>,
+    pub make_current: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct GameManifest {
+    pub game_id: String,
+    pub status: String,
+    pub file_count: i64,
+    pub total_size: i64,
+    pub error: Option<String>,
+    pub generated_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(skip_serializing)]
+    pub fingerprint: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct GameFile {
+    #[serde(skip_serializing)]
+    pub id: String,
+    #[serde(skip_serializing)]
+    pub game_id: String,
+    pub relative_path: String,
+    pub size: i64,
+    pub sha256: String,
+    pub modified_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ManifestResponse {
+    #[serde(flatten)]
+    pub manifest: GameManifest,
+    pub files: Vec<GameFile>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PurgeCacheResponse {
+    pub purged: u64,
+}
+
+// Provider-neutral game metadata; every MetadataProvider maps its own API into this
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameMetadata {
+    pub provider: String,
+    pub external_id: String,
+    pub name: String,
+    pub summary: Option<String>,
+    pub storyline: Option<String>,
+    pub rating: Option<f64>, // 0-100
+    pub release_date: Option<DateTime<Utc>>,
+    pub cover_url: Option<String>,
+    #[serde(default)]
+    pub screenshots: Vec<String>,
+    #[serde(default)]
+    pub genres: Vec<String>,
+    #[serde(default)]
+    pub platforms: Vec<String>,
+    pub developer: Option<String>,
+    pub publisher: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct MetadataMatch {
+    pub id: String,
+    pub game_id: String,
+    pub game_name: String,
+    pub provider: String,
+    pub external_id: String,
+    pub candidate_name: String,
+    pub release_year: Option<i64>,
+    pub score: f64,
+    #[serde(serialize_with = "json_text")]
+    pub metadata: String, // GameMetadata as JSON
+    pub status: String,
+    pub resolved_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MetadataMatchQuery {
+    pub status: Option<String>,
+    pub game_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QueuedJobsResponse {
+    pub queued: usize,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Job {
+    pub id: String,
+    pub kind: String,
+    #[serde(serialize_with = "json_text")]
+    pub payload: String,
+    pub status: String,
+    pub progress: f64,
+    pub message: Option<String>,
+    #[serde(serialize_with = "optional_json_text")]
+    pub result: Option<String>,
+    pub error: Option<String>,
+    pub attempts: i64,
+    pub max_attempts: i64,
+    pub cancel_requested: bool,
+    pub created_by: Option<String>,
+    pub run_after: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JobListQuery {
+    pub status: Option<String>,
+    pub kind: Option<String>,
+    pub limit: Option<i64>,
+}
+
+// Bookkeeping row for a recurring maintenance task
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ScheduledTaskRecord {
+    pub name: String,
+    pub last_status: Option<String>,
+    pub last_message: Option<String>,
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_finished_at: Option<DateTime<Utc>>,
+    pub next_run_at: Option<DateTime<Utc>>,
+}
+
+// A task as configured, with its last run. schedule is None when the task is turned off.
+#[derive(Debug, Serialize)]
+pub struct ScheduledTaskStatus {
+    pub name: String,
+    pub description: String,
+    pub schedule: Option<String>,
+    pub last_status: Option<String>,
+    pub last_message: Option<String>,
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_finished_at: Option<DateTime<Utc>>,
+    pub next_run_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessEffect {
+    Allow,
+    Deny,
+}
+
+impl AccessEffect {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccessEffect::Allow => "allow",
+            AccessEffect::Deny => "deny",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct UserGroup {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub member_count: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateGroupRequest {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct GroupMember {
+    pub user_id: String,
+    pub username: String,
+    pub added_at: DateTime<Utc>,
+}
+
+// Exactly one of user_id/group_id is set, and exactly one of game_id/library_root
+#[derive(Debug, Serialize, FromRow)]
+pub struct AccessRule {
+    pub id: String,
+    pub user_id: Option<String>,
+    pub group_id: Option<String>,
+    pub game_id: Option<String>,
+    pub library_root: Option<String>,
+    pub effect: String,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAccessRuleRequest {
+    pub user_id: Option<String>,
+    pub group_id: Option<String>,
+    pub game_id: Option<String>,
+    pub library_root: Option<String>,
+    pub effect: AccessEffect,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AccessRuleQuery {
+    pub user_id: Option<String>,
+    pub group_id: Option<String>,
+    pub game_id: Option<String>,
+}
+
+// Emit a JSON text column as a nested object rather than an escaped string
+fn json_text<S: serde::Serializer>(raw: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    match serde_json::from_str::<serde_json::Value>(raw) {
+        Ok(value) => value.serialize(serializer),
+        Err(_) => serializer.serialize_str(raw),
+    }
+}
+
+fn optional_json_text<S: serde::Serializer>(raw: &Option<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    match raw {
+        Some(raw) => json_text(raw, serializer),
+        None => serializer.serialize_none(),
+    }
 }
 
 // IGDB API Response structures - Added Serialize trait to ALL structs
@@ -0,0 +1,272 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tokio::sync::RwLock;
+
+// Signing keys are fetched again at most this often when a token names an unknown key
+const JWKS_REFETCH_INTERVAL: Duration = Duration::from_secs(60);
+
+// Accepted ID token signatures; HMAC is left out so the client secret can never be used
+// to forge a token
+const ALLOWED_ALGORITHMS: &[Algorithm] = &[
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    // None for public clients, which rely on PKCE alone
+    pub client_secret: Option<String>,
+    // Must point at /api/auth/oidc/callback and be registered with the provider
+    pub redirect_url: String,
+    pub scopes: String,
+    pub groups_claim: String,
+    // Members of any of these groups are superusers; everyone else is not
+    pub admin_groups: Vec<String>,
+    // Provider group -> local role name. Only mapped roles are added or removed on login.
+    pub role_mapping: Vec<(String, String)>,
+    // Create accounts for identities that are not linked to a local user yet
+    pub auto_create_users: bool,
+    // Absolute URLs (besides local paths) the callback may send the browser back to,
+    // e.g. a desktop client's custom scheme
+    pub allowed_redirects: Vec<String>,
+}
+
+// Where the callback may send the browser, with the tokens in the fragment. Plain local paths
+// are always fine; absolute URLs must match an allowed entry's scheme, host and port exactly
+// and sit under its path.
+pub fn redirect_allowed(redirect_to: &str, allowed_redirects: &[String]) -> bool {
+    // Browsers drop tabs and newlines from URLs, so "/\t/evil.com" would become "//evil.com"
+    if redirect_to.is_empty() || redirect_to.chars().any(|c| c.is_control() || c.is_whitespace() || c == '\\') {
+        return false;
+    }
+
+    if redirect_to.starts_with('/') {
+        // Resolving against a placeholder origin catches scheme-relative and other tricks
+        let base = url::Url::parse("http://local.invalid/").expect("valid base URL");
+        return !redirect_to.starts_with("//")
+            && base.join(redirect_to).is_ok_and(|url| url.origin() == base.origin());
+    }
+
+    let Ok(url) = url::Url::parse(redirect_to) else { return false };
+    if !url.username().is_empty() || url.password().is_some() {
+        return false;
+    }
+    allowed_redirects.iter().filter_map(|allowed| url::Url::parse(allowed).ok()).any(|allowed| {
+        let prefix = allowed.path();
+        let path = url.path();
+        url.scheme() == allowed.scheme()
+            && url.host_str() == allowed.host_str()
+            && url.port_or_known_default() == allowed.port_or_known_default()
+            && (path == prefix
+                || (path.starts_with(prefix) && (prefix.ends_with('/') || path[prefix.len()..].starts_with('/'))))
+    })
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+}
+
+// Standard claims we use, plus everything else for the configurable groups claim
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    pub preferred_username: Option<String>,
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl IdTokenClaims {
+    // None when the provider did not send the claim at all, as opposed to an empty list
+    pub fn groups(&self, claim: &str) -> Option<Vec<String>> {
+        match self.extra.get(claim)? {
+            serde_json::Value::Array(values) => Some(
+                values
+                    .iter()
+                    .filter_map(|value| value.as_str().map(str::to_string))
+                    .collect(),
+            ),
+            serde_json::Value::String(value) => Some(value.split(',').map(|group| group.trim().to_string()).collect()),
+            _ => None,
+        }
+    }
+}
+
+pub struct OidcClient {
+    config: OidcConfig,
+    http: reqwest::Client,
+    // Discovered lazily so the server still starts while the provider is down
+    metadata: RwLock<Option<ProviderMetadata>>,
+    jwks: RwLock<Option<(JwkSet, Instant)>>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+            metadata: RwLock::new(None),
+            jwks: RwLock::new(None),
+        }
+    }
+
+    pub fn config(&self) -> &OidcConfig {
+        &self.config
+    }
+
+    async fn metadata(&self) -> Result<ProviderMetadata> {
+        if let Some(metadata) = self.metadata.read().await.as_ref() {
+            return Ok(metadata.clone());
+        }
+
+        let url = format!("{}/.well-known/openid-configuration", self.config.issuer.trim_end_matches('/'));
+        let metadata: ProviderMetadata = self
+            .http
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .map_err(|e| anyhow!("invalid discovery document at {}: {}", url, e))?;
+
+        if metadata.issuer.trim_end_matches('/') != self.config.issuer.trim_end_matches('/') {
+            return Err(anyhow!(
+                "discovery document is for issuer {}, expected {}",
+                metadata.issuer,
+                self.config.issuer
+            ));
+        }
+
+        *self.metadata.write().await = Some(metadata.clone());
+        Ok(metadata)
+    }
+
+    // Where to send the browser to sign in
+    pub async fn authorization_url(&self, state: &str, nonce: &str, code_verifier: &str) -> Result<String> {
+        let metadata = self.metadata().await?;
+        let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+        let url = url::Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("scope", self.config.scopes.as_str()),
+                ("state", state),
+                ("nonce", nonce),
+                ("code_challenge", code_challenge.as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )?;
+        Ok(url.to_string())
+    }
+
+    // Trades the authorization code for an ID token and returns its validated claims
+    pub async fn exchange_code(&self, code: &str, code_verifier: &str, nonce: &str) -> Result<IdTokenClaims> {
+        let metadata = self.metadata().await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_url.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        let mut request = self.http.post(&metadata.token_endpoint);
+        match &self.config.client_secret {
+            Some(secret) => request = request.basic_auth(&self.config.client_id, Some(secret)),
+            None => form.push(("client_id", self.config.client_id.as_str())),
+        }
+
+        let response = request.form(&form).send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow!("token endpoint returned {}: {}", status, body));
+        }
+        let tokens: TokenResponse = response.json().await?;
+        let id_token = tokens.id_token.ok_or_else(|| anyhow!("token response has no id_token"))?;
+
+        self.validate_id_token(&id_token, &metadata, nonce).await
+    }
+
+    async fn validate_id_token(&self, id_token: &str, metadata: &ProviderMetadata, nonce: &str) -> Result<IdTokenClaims> {
+        let header = jsonwebtoken::decode_header(id_token)?;
+        if !ALLOWED_ALGORITHMS.contains(&header.alg) {
+            return Err(anyhow!("ID token signed with unsupported algorithm {:?}", header.alg));
+        }
+        let key = self.decoding_key(&metadata.jwks_uri, header.kid.as_deref()).await?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["iss", "sub", "aud", "exp"]);
+        validation.leeway = 60;
+
+        let claims = jsonwebtoken::decode::<IdTokenClaims>(id_token, &key, &validation)?.claims;
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(anyhow!("ID token nonce does not match the login request"));
+        }
+        // A token issued to another client that merely lists us as an audience
+        if let Some(azp) = claims.extra.get("azp").and_then(|azp| azp.as_str()) {
+            if azp != self.config.client_id {
+                return Err(anyhow!("ID token was issued to client {}", azp));
+            }
+        }
+        Ok(claims)
+    }
+
+    async fn decoding_key(&self, jwks_uri: &str, kid: Option<&str>) -> Result<DecodingKey> {
+        let find = |jwks: &JwkSet| match kid {
+            Some(kid) => jwks.find(kid).cloned(),
+            None => jwks.keys.first().cloned(),
+        };
+
+        let stale = match self.jwks.read().await.as_ref() {
+            Some((jwks, fetched_at)) => match find(jwks) {
+                Some(jwk) => return Ok(DecodingKey::from_jwk(&jwk)?),
+                None => fetched_at.elapsed() >= JWKS_REFETCH_INTERVAL,
+            },
+            None => true,
+        };
+        if !stale {
+            return Err(anyhow!("no signing key {:?} at {}", kid, jwks_uri));
+        }
+
+        // The provider may have rotated its keys
+        let jwks: JwkSet = self.http.get(jwks_uri).send().await?.error_for_status()?.json().await?;
+        let jwk = find(&jwks);
+        *self.jwks.write().await = Some((jwks, Instant::now()));
+
+        let jwk = jwk.ok_or_else(|| anyhow!("no signing key {:?} at {}", kid, jwks_uri))?;
+        Ok(DecodingKey::from_jwk(&jwk)?)
+    }
+}
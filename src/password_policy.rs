@@ -0,0 +1,53 @@
+use anyhow::Result;
+use std::{collections::HashSet, path::Path};
+
+// bcrypt only looks at the first 72 bytes; longer passwords would silently lose their tail
+const MAX_PASSWORD_BYTES: usize = 72;
+
+pub struct PasswordPolicy {
+    min_length: usize,
+    // Lowercased known-breached passwords, one per line in the source file
+    breached: HashSet<String>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self { min_length: 8, breached: HashSet::new() }
+    }
+}
+
+impl PasswordPolicy {
+    pub fn new(min_length: usize, breached_list: Option<&Path>) -> Result<Self> {
+        let breached = match breached_list {
+            Some(path) => std::fs::read_to_string(path)?
+                .lines()
+                .map(|line| line.trim().to_lowercase())
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .collect(),
+            None => HashSet::new(),
+        };
+
+        Ok(Self { min_length, breached })
+    }
+
+    pub fn breached_count(&self) -> usize {
+        self.breached.len()
+    }
+
+    // A message for the user when the password is not acceptable
+    pub fn check(&self, password: &str, username: &str) -> Result<(), String> {
+        if password.chars().count() < self.min_length {
+            return Err(format!("Password must be at least {} characters", self.min_length));
+        }
+        if password.len() > MAX_PASSWORD_BYTES {
+            return Err(format!("Password must be at most {} bytes", MAX_PASSWORD_BYTES));
+        }
+        if password.eq_ignore_ascii_case(username.trim()) {
+            return Err("Password must not be the same as the username".to_string());
+        }
+        if self.breached.contains(&password.to_lowercase()) {
+            return Err("Password appears in a list of breached passwords; choose another".to_string());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use axum::{extract::FromRequestParts, http::{request::Parts, StatusCode}};
+use serde::Serialize;
+use std::{collections::HashSet, marker::PhantomData};
+
+// Everything a role can grant. The built-in superuser role has all of them.
+pub const PERMISSIONS: &[(&str, &str)] = &[
+    ("users:manage", "Manage users, their sessions and passwords, groups and game access rules"),
+    ("roles:manage", "Create roles and assign them to users"),
+    ("games:upload", "Add games and builds and scan the library roots"),
+    ("games:edit", "Edit games, availability, manifests, media and current builds"),
+    ("games:delete", "Delete games"),
+    ("metadata:edit", "Search metadata providers and fetch, match and refresh metadata"),
+    ("jobs:manage", "View and cancel background jobs and the maintenance schedule"),
+    ("catalog:manage", "Export and import the catalog"),
+];
+
+// Role every existing admin was migrated to; users.is_admin mirrors membership in it
+pub const SUPERUSER_ROLE_ID: &str = "superuser";
+
+// What the authenticated user may do on the admin routes, resolved from their roles
+#[derive(Debug, Clone, Default)]
+pub struct Permissions {
+    all: bool,
+    granted: HashSet<String>,
+}
+
+impl Permissions {
+    pub fn all() -> Self {
+        Self { all: true, granted: HashSet::new() }
+    }
+
+    pub fn from_granted(granted: impl IntoIterator<Item = String>) -> Self {
+        Self { all: false, granted: granted.into_iter().collect() }
+    }
+
+    pub fn contains(&self, permission: &str) -> bool {
+        self.all || self.granted.contains(permission)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.all && self.granted.is_empty()
+    }
+
+    // Whether these include everything `other` grants, so acting on a user with `other`
+    // cannot be used to gain more than we already have
+    pub fn covers(&self, other: &Permissions) -> bool {
+        self.all || (!other.all && other.granted.is_subset(&self.granted))
+    }
+}
+
+// What membership in a role grants; the superuser role grants everything, listed or not
+pub fn role_grants(role_id: &str, permissions: &[String]) -> Permissions {
+    if role_id == SUPERUSER_ROLE_ID {
+        Permissions::all()
+    } else {
+        Permissions::from_granted(permissions.iter().cloned())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PermissionInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+pub fn all_permissions() -> Vec<PermissionInfo> {
+    PERMISSIONS
+        .iter()
+        .map(|(name, description)| PermissionInfo { name, description })
+        .collect()
+}
+
+pub fn is_known(permission: &str) -> bool {
+    PERMISSIONS.iter().any(|(known, _)| *known == permission)
+}
+
+pub trait Permission {
+    const NAME: &'static str;
+}
+
+pub struct ManageUsers;
+pub struct ManageRoles;
+pub struct UploadGames;
+pub struct EditGames;
+pub struct DeleteGames;
+pub struct EditMetadata;
+pub struct ManageJobs;
+pub struct ManageCatalog;
+
+impl Permission for ManageUsers { const NAME: &'static str = "users:manage"; }
+impl Permission for ManageRoles { const NAME: &'static str = "roles:manage"; }
+impl Permission for UploadGames { const NAME: &'static str = "games:upload"; }
+impl Permission for EditGames { const NAME: &'static str = "games:edit"; }
+impl Permission for DeleteGames { const NAME: &'static str = "games:delete"; }
+impl Permission for EditMetadata { const NAME: &'static str = "metadata:edit"; }
+impl Permission for ManageJobs { const NAME: &'static str = "jobs:manage"; }
+impl Permission for ManageCatalog { const NAME: &'static str = "catalog:manage"; }
+
+// Add `_: RequirePermission<EditMetadata>` to a handler to gate it. Only works behind
+// admin_middleware, which resolves the user's permissions; anywhere else it rejects.
+pub struct RequirePermission<P>(PhantomData<P>);
+
+impl<P: Permission, S: Send + Sync> FromRequestParts<S> for RequirePermission<P> {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let permissions = parts.extensions.get::<Permissions>().ok_or(StatusCode::FORBIDDEN)?;
+        if permissions.contains(P::NAME) {
+            Ok(Self(PhantomData))
+        } else {
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use reqwest::{Client, StatusCode};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use chrono::{NaiveDate, TimeZone, Utc};
+use serde::Deserialize;
+use crate::{
+    metadata_provider::{MetadataProvider, ProviderCapabilities},
+    models::GameMetadata,
+};
+
+// RAWG API response structures (only the fields we map)
+#[derive(Debug, Deserialize)]
+struct RawgSearchResponse {
+    results: Vec<RawgGame>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawgGame {
+    id: i64,
+    name: String,
+    description_raw: Option<String>,
+    released: Option<String>,
+    background_image: Option<String>,
+    rating: Option<f64>,
+    metacritic: Option<f64>,
+    #[serde(default)]
+    genres: Vec<RawgNamed>,
+    #[serde(default)]
+    platforms: Option<Vec<RawgPlatformEntry>>,
+    #[serde(default)]
+    developers: Vec<RawgNamed>,
+    #[serde(default)]
+    publishers: Vec<RawgNamed>,
+    #[serde(default)]
+    short_screenshots: Vec<RawgScreenshot>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawgNamed {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawgPlatformEntry {
+    platform: RawgNamed,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawgScreenshot {
+    image: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawgScreenshotsResponse {
+    results: Vec<RawgScreenshot>,
+}
+
+pub struct RawgClient {
+    client: Client,
+    api_key: String,
+    base_url: String,
+}
+
+impl RawgClient {
+    pub fn new(api_key: String, base_url: String) -> Self {
+        Self {
+            client: Client::new(),
+            api_key,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<Option<T>> {
+        let response = self.client
+            .get(format!("{}{}", self.base_url, path))
+            .query(&[("key", self.api_key.as_str())])
+            .query(query)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        if !response.status().is_success() {
+            return Err(anyhow!("RAWG API request failed: {}", response.status()));
+        }
+
+        Ok(Some(response.json().await?))
+    }
+}
+
+#[async_trait]
+impl MetadataProvider for RawgClient {
+    fn id(&self) -> &'static str {
+        "rawg"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "RAWG"
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            search: true,
+            fetch_by_id: true,
+            screenshots: true,
+            storyline: false,
+            ratings: true,
+        }
+    }
+
+    async fn search(&self, query: &str, limit: u32) -> Result<Vec<GameMetadata>> {
+        let response: Option<RawgSearchResponse> = self
+            .get("/games", &[("search", query.to_string()), ("page_size", limit.to_string())])
+            .await?;
+
+        Ok(response
+            .map(|r| r.results.into_iter().map(to_metadata).collect())
+            .unwrap_or_default())
+    }
+
+    async fn fetch(&self, external_id: &str) -> Result<Option<GameMetadata>> {
+        if external_id.is_empty() || !external_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(anyhow!("invalid RAWG id: {}", external_id));
+        }
+
+        let game: Option<RawgGame> = self.get(&format!("/games/{}", external_id), &[]).await?;
+        let mut game = match game {
+            Some(game) => game,
+            None => return Ok(None),
+        };
+
+        // The detail endpoint has no screenshots; they live on their own sub-resource
+        let screenshots: Option<RawgScreenshotsResponse> = self
+            .get(&format!("/games/{}/screenshots", external_id), &[])
+            .await?;
+        if let Some(screenshots) = screenshots {
+            game.short_screenshots = screenshots.results;
+        }
+
+        Ok(Some(to_metadata(game)))
+    }
+}
+
+fn to_metadata(game: RawgGame) -> GameMetadata {
+    let release_date = game
+        .released
+        .as_deref()
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|date| Utc.from_utc_datetime(&date));
+
+    // RAWG user ratings are 0-5; prefer the 0-100 Metacritic score when there is one
+    let rating = game
+        .metacritic
+        .or_else(|| game.rating.filter(|r| *r > 0.0).map(|r| r * 20.0));
+
+    GameMetadata {
+        provider: "rawg".to_string(),
+        external_id: game.id.to_string(),
+        name: game.name,
+        summary: game.description_raw.filter(|d| !d.is_empty()),
+        storyline: None,
+        rating,
+        release_date,
+        cover_url: game.background_image,
+        screenshots: game.short_screenshots.into_iter().map(|s| s.image).collect(),
+        genres: game.genres.into_iter().map(|g| g.name).collect(),
+        platforms: game
+            .platforms
+            .unwrap_or_default()
+            .into_iter()
+            .map(|p| p.platform.name)
+            .collect(),
+        developer: game.developers.into_iter().next().map(|d| d.name),
+        publisher: game.publishers.into_iter().next().map(|p| p.name),
+    }
+}
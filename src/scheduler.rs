@@ -0,0 +1,362 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use std::{str::FromStr, time::Duration};
+use crate::{
+    handlers::AppState,
+    jobs::{self, JobPayload},
+    models::ScheduledTaskStatus,
+};
+
+// Finished jobs are kept this long for the admin job list before database maintenance drops them
+const JOB_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaintenanceTask {
+    SessionCleanup,
+    MetadataRefresh,
+    LibraryRescan,
+    DatabaseOptimize,
+}
+
+impl MaintenanceTask {
+    pub const ALL: &'static [MaintenanceTask] = &[
+        MaintenanceTask::SessionCleanup,
+        MaintenanceTask::MetadataRefresh,
+        MaintenanceTask::LibraryRescan,
+        MaintenanceTask::DatabaseOptimize,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MaintenanceTask::SessionCleanup => "session_cleanup",
+            MaintenanceTask::MetadataRefresh => "metadata_refresh",
+            MaintenanceTask::LibraryRescan => "library_rescan",
+            MaintenanceTask::DatabaseOptimize => "database_optimize",
+        }
+    }
+
+    // Environment variable that overrides the schedule
+    pub fn env_var(&self) -> &'static str {
+        match self {
+            MaintenanceTask::SessionCleanup => "SCHEDULE_SESSION_CLEANUP",
+            MaintenanceTask::MetadataRefresh => "SCHEDULE_METADATA_REFRESH",
+            MaintenanceTask::LibraryRescan => "SCHEDULE_LIBRARY_RESCAN",
+            MaintenanceTask::DatabaseOptimize => "SCHEDULE_DATABASE_OPTIMIZE",
+        }
+    }
+
+    pub fn default_schedule(&self) -> &'static str {
+        match self {
+            MaintenanceTask::SessionCleanup => "1h",
+            MaintenanceTask::MetadataRefresh => "0 3 * * *",
+            MaintenanceTask::LibraryRescan => "6h",
+            MaintenanceTask::DatabaseOptimize => "30 4 * * Sun",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            MaintenanceTask::SessionCleanup => "Delete expired login sessions",
+            MaintenanceTask::MetadataRefresh => "Queue metadata refreshes for games with stale metadata",
+            MaintenanceTask::LibraryRescan => "Queue a scan of the library roots",
+            MaintenanceTask::DatabaseOptimize => "Prune old jobs and cache entries, then optimize and vacuum the database",
+        }
+    }
+}
+
+pub enum Schedule {
+    Every(Duration),
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    // "off" disables a task. Otherwise either an interval such as "30m", "6h" or "1d",
+    // or a cron expression in UTC ("0 3 * * *"; a leading seconds field is optional).
+    pub fn parse(spec: &str) -> Result<Option<Schedule>> {
+        let spec = spec.trim();
+        if spec.is_empty() || spec.eq_ignore_ascii_case("off") {
+            return Ok(None);
+        }
+
+        if let Some(interval) = parse_interval(spec) {
+            if interval.is_zero() {
+                return Err(anyhow!("schedule interval must be greater than zero"));
+            }
+            return Ok(Some(Schedule::Every(interval)));
+        }
+
+        let expression = if spec.split_whitespace().count() == 5 {
+            format!("0 {}", spec)
+        } else {
+            spec.to_string()
+        };
+        let schedule = cron::Schedule::from_str(&expression)
+            .map_err(|e| anyhow!("invalid schedule '{}': {}", spec, e))?;
+        Ok(Some(Schedule::Cron(Box::new(schedule))))
+    }
+
+    // Intervals count from the previous run so restarts do not reset them; a run that was
+    // missed while the server was down happens straight away
+    fn next_run(&self, now: DateTime<Utc>, last_started: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+        match self {
+            Schedule::Every(interval) => {
+                let interval = chrono::Duration::from_std(*interval).ok()?;
+                Some(last_started.map_or(now + interval, |last| (last + interval).max(now)))
+            }
+            Schedule::Cron(schedule) => schedule.after(&now).next(),
+        }
+    }
+}
+
+fn parse_interval(spec: &str) -> Option<Duration> {
+    let unit = spec.chars().last()?;
+    let amount: u64 = spec[..spec.len() - unit.len_utf8()].parse().ok()?;
+    let seconds = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Duration::from_secs(amount.checked_mul(seconds)?))
+}
+
+struct ScheduledTask {
+    task: MaintenanceTask,
+    spec: String,
+    schedule: Option<Schedule>,
+}
+
+pub struct Scheduler {
+    tasks: Vec<ScheduledTask>,
+    metadata_stale_after: Duration,
+}
+
+impl Scheduler {
+    pub fn new(specs: Vec<(MaintenanceTask, String)>, metadata_stale_after: Duration) -> Result<Self> {
+        let tasks = specs
+            .into_iter()
+            .map(|(task, spec)| {
+                let schedule = Schedule::parse(&spec)
+                    .map_err(|e| anyhow!("{}: {}", task.env_var(), e))?;
+                Ok(ScheduledTask { task, spec, schedule })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { tasks, metadata_stale_after })
+    }
+
+    pub fn enabled_count(&self) -> usize {
+        self.tasks.iter().filter(|task| task.schedule.is_some()).count()
+    }
+
+    // Every configured task with what the database remembers about its last run
+    pub async fn status(&self, state: &AppState) -> Result<Vec<ScheduledTaskStatus>> {
+        let records = state.db.get_scheduled_tasks().await?;
+
+        Ok(self
+            .tasks
+            .iter()
+            .map(|scheduled| {
+                let record = records.iter().find(|record| record.name == scheduled.task.name());
+                ScheduledTaskStatus {
+                    name: scheduled.task.name().to_string(),
+                    description: scheduled.task.description().to_string(),
+                    schedule: scheduled.schedule.as_ref().map(|_| scheduled.spec.clone()),
+                    last_status: record.and_then(|record| record.last_status.clone()),
+                    last_message: record.and_then(|record| record.last_message.clone()),
+                    last_started_at: record.and_then(|record| record.last_started_at),
+                    last_finished_at: record.and_then(|record| record.last_finished_at),
+                    // A disabled task has no next run, whatever was recorded before
+                    next_run_at: scheduled.schedule.as_ref().and(record.and_then(|record| record.next_run_at)),
+                }
+            })
+            .collect())
+    }
+}
+
+pub async fn start(state: &AppState) -> Result<()> {
+    state.db.fail_interrupted_scheduled_tasks().await?;
+
+    for (index, scheduled) in state.scheduler.tasks.iter().enumerate() {
+        if scheduled.schedule.is_none() {
+            tracing::info!("Scheduled task {} is disabled", scheduled.task.name());
+            continue;
+        }
+
+        let state = state.clone();
+        tokio::spawn(async move {
+            let scheduled = &state.scheduler.tasks[index];
+            let (task, schedule) = match &scheduled.schedule {
+                Some(schedule) => (scheduled.task, schedule),
+                None => return,
+            };
+
+            let mut last_started = match state.db.get_scheduled_task(task.name()).await {
+                Ok(record) => record.and_then(|record| record.last_started_at),
+                Err(e) => {
+                    tracing::warn!("Failed to load last run of {}: {}", task.name(), e);
+                    None
+                }
+            };
+
+            loop {
+                let now = Utc::now();
+                let next = match schedule.next_run(now, last_started) {
+                    Some(next) => next,
+                    None => {
+                        tracing::warn!("Scheduled task {} has no upcoming run", task.name());
+                        return;
+                    }
+                };
+                if let Err(e) = state.db.set_scheduled_task_next_run(task.name(), next).await {
+                    tracing::warn!("Failed to record next run of {}: {}", task.name(), e);
+                }
+
+                tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+                last_started = Some(run_task(&state, task).await);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+async fn run_task(state: &AppState, task: MaintenanceTask) -> DateTime<Utc> {
+    let started_at = Utc::now();
+    tracing::info!("Running scheduled task {}", task.name());
+    if let Err(e) = state.db.start_scheduled_task(task.name(), started_at).await {
+        tracing::warn!("Failed to record start of {}: {}", task.name(), e);
+    }
+
+    let (status, message) = match execute(state, task).await {
+        Ok(message) => {
+            tracing::info!("Scheduled task {} succeeded: {}", task.name(), message);
+            ("succeeded", message)
+        }
+        Err(e) => {
+            tracing::error!("Scheduled task {} failed: {}", task.name(), e);
+            ("failed", e.to_string())
+        }
+    };
+    if let Err(e) = state.db.finish_scheduled_task(task.name(), status, &message).await {
+        tracing::warn!("Failed to record outcome of {}: {}", task.name(), e);
+    }
+
+    started_at
+}
+
+// Returns a short summary of what the run did
+async fn execute(state: &AppState, task: MaintenanceTask) -> Result<String> {
+    match task {
+        MaintenanceTask::SessionCleanup => {
+            let deleted = state
+                .auth_service
+                .cleanup_expired_sessions()
+                .await
+                .map_err(|e| anyhow!("failed to delete expired sessions: {}", e))?;
+            Ok(format!("Deleted {} expired session(s)", deleted))
+        }
+        MaintenanceTask::MetadataRefresh => {
+            let cutoff = Utc::now() - chrono::Duration::from_std(state.scheduler.metadata_stale_after)?;
+            let game_ids = state.db.get_stale_metadata_game_ids(cutoff).await?;
+            let count = game_ids.len();
+            for game_id in game_ids {
+                jobs::enqueue(state, JobPayload::RefreshMetadata { game_id }, None).await?;
+            }
+            Ok(format!("Queued metadata refresh for {} game(s)", count))
+        }
+        MaintenanceTask::LibraryRescan => {
+            if state.library_scanner.roots().is_empty() {
+                return Ok("No library roots configured".to_string());
+            }
+            let job = jobs::enqueue(state, JobPayload::ScanLibrary, None).await?;
+            Ok(format!("Queued library scan job {}", job.id))
+        }
+        MaintenanceTask::DatabaseOptimize => {
+            let jobs = state.db.purge_finished_jobs(Utc::now() - chrono::Duration::from_std(JOB_RETENTION)?).await?;
+            let cache_entries = state.igdb_client.purge_cache(true).await?;
+            state.db.optimize().await?;
+            Ok(format!(
+                "Pruned {} finished job(s) and {} expired IGDB cache entries; database optimized",
+                jobs, cache_entries
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 6, 10, hour, minute, 0).unwrap()
+    }
+
+    fn every(spec: &str) -> Duration {
+        match Schedule::parse(spec) {
+            Ok(Some(Schedule::Every(interval))) => interval,
+            _ => panic!("'{}' should parse as an interval", spec),
+        }
+    }
+
+    #[test]
+    fn off_and_empty_disable_a_task() {
+        for spec in ["off", "OFF", "", "  "] {
+            assert!(matches!(Schedule::parse(spec), Ok(None)), "'{}'", spec);
+        }
+    }
+
+    #[test]
+    fn parses_intervals() {
+        assert_eq!(every("45s"), Duration::from_secs(45));
+        assert_eq!(every("30m"), Duration::from_secs(30 * 60));
+        assert_eq!(every(" 6h "), Duration::from_secs(6 * 60 * 60));
+        assert_eq!(every("1d"), Duration::from_secs(24 * 60 * 60));
+    }
+
+    #[test]
+    fn rejects_zero_and_malformed_intervals() {
+        assert!(Schedule::parse("0m").is_err());
+        for spec in ["h", "-5m", "5w", "1.5h", "99999999999999999d"] {
+            assert!(Schedule::parse(spec).is_err(), "'{}'", spec);
+        }
+    }
+
+    #[test]
+    fn parses_cron_with_or_without_seconds() {
+        for spec in ["0 3 * * *", "0 0 3 * * *", "30 4 * * Sun"] {
+            assert!(matches!(Schedule::parse(spec), Ok(Some(Schedule::Cron(_)))), "'{}'", spec);
+        }
+        for spec in ["0 3 * *", "61 3 * * *", "every day"] {
+            assert!(Schedule::parse(spec).is_err(), "'{}'", spec);
+        }
+    }
+
+    #[test]
+    fn default_schedules_parse() {
+        for task in MaintenanceTask::ALL {
+            assert!(matches!(Schedule::parse(task.default_schedule()), Ok(Some(_))), "{}", task.name());
+        }
+    }
+
+    #[test]
+    fn intervals_count_from_the_last_run() {
+        let schedule = Schedule::parse("6h").unwrap().unwrap();
+        let now = at(12, 0);
+
+        assert_eq!(schedule.next_run(now, None), Some(at(18, 0)));
+        assert_eq!(schedule.next_run(now, Some(at(10, 0))), Some(at(16, 0)));
+        // A run missed while the server was down happens now
+        assert_eq!(schedule.next_run(now, Some(at(2, 0))), Some(now));
+    }
+
+    #[test]
+    fn cron_runs_at_the_next_match() {
+        let schedule = Schedule::parse("0 3 * * *").unwrap().unwrap();
+
+        assert_eq!(schedule.next_run(at(2, 59), None), Some(at(3, 0)));
+        assert_eq!(schedule.next_run(at(3, 0), Some(at(3, 0))), Some(at(3, 0) + chrono::Duration::days(1)));
+    }
+}
@@ -1,14 +1,16 @@
 use axum::{
     extract::{Extension, State, Path, Query},
-    http::StatusCode,
-    response::Json,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
 };
 use axum_macros::debug_handler;
 use serde::{Deserialize, Serialize};
 use crate::{
     auth::User,
-    handlers::{AppState, ApiResponse, PaginationQuery},
-    database::UserGameWithDetails,
+    handlers::{AppState, ApiResponse, GameListQuery},
+    database::{GameScope, UserGameWithDetails, Viewer},
+    downloads,
+    models::{ManifestResponse, GameBuildResponse, GameFacets},
 };
 
 #[derive(Deserialize)]
@@ -22,6 +24,7 @@ pub struct UserLibraryResponse {
     pub total: i64,
     pub page: i64,
     pub per_page: i64,
+    pub facets: GameFacets,
 }
 
 #[derive(Serialize)]
@@ -32,6 +35,9 @@ pub struct UserGameResponse {
     pub installed_at: Option<chrono::DateTime<chrono::Utc>>,
     pub last_played: Option<chrono::DateTime<chrono::Utc>>,
     pub play_time_minutes: i64,
+    pub installed_build_id: Option<String>,
+    pub current_build_id: Option<String>,
+    pub update_available: bool,
     pub game: GameSummary,
 }
 
@@ -49,6 +55,11 @@ pub struct GameSummary {
 
 impl From<UserGameWithDetails> for UserGameResponse {
     fn from(user_game: UserGameWithDetails) -> Self {
+        // Only an installed copy of a different build than the current one counts as outdated
+        let update_available = user_game.is_installed
+            && user_game.current_build_id.is_some()
+            && user_game.installed_build_id != user_game.current_build_id;
+
         Self {
             user_game_id: user_game.user_game_id,
             is_installed: user_game.is_installed,
@@ -56,6 +67,9 @@ impl From<UserGameWithDetails> for UserGameResponse {
             installed_at: user_game.installed_at,
             last_played: user_game.last_played,
             play_time_minutes: user_game.play_time_minutes,
+            installed_build_id: user_game.installed_build_id,
+            current_build_id: user_game.current_build_id,
+            update_available,
             game: GameSummary {
                 id: user_game.id,
                 name: user_game.name,
@@ -70,29 +84,61 @@ impl From<UserGameWithDetails> for UserGameResponse {
     }
 }
 
+// Access rules decide which games the user sees; superusers see everything
+fn viewer<'a>(state: &AppState, user: &'a User) -> Viewer<'a> {
+    Viewer {
+        user_id: &user.id,
+        unrestricted: user.is_admin,
+        default_access: state.default_game_access,
+    }
+}
+
+// Games hidden from the user are reported as missing rather than forbidden
+async fn ensure_visible(state: &AppState, user: &User, game_id: &str) -> Result<(), StatusCode> {
+    match state.db.is_game_visible(viewer(state, user), game_id).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to check game access: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
 // Get available games (store catalog)
 #[debug_handler]
 #[allow(unused_variables)]
 pub async fn get_store_games(
     State(state): State<AppState>,
     Extension(user): Extension<User>,
-    Query(params): Query<PaginationQuery>,
+    Query(params): Query<GameListQuery>,
 ) -> Result<Json<ApiResponse<crate::models::GameListResponse>>, StatusCode> {
     let page = params.page.unwrap_or(1);
     let per_page = params.per_page.unwrap_or(20);
 
-    match state.db.get_available_games(page, per_page).await {
-        Ok((games, total)) => {
+    let filter = params.filter();
+
+    let (games, total) = match state.db.get_available_games(viewer(&state, &user), &filter, page, per_page).await {
+        Ok(result) => result,
+        Err(e) => {
+            tracing::error!("Failed to get store games: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    match state.db.get_game_facets(&filter, GameScope::Available(viewer(&state, &user))).await {
+        Ok(facets) => {
             let response = crate::models::GameListResponse {
                 games,
                 total,
                 page,
                 per_page,
+                facets,
             };
             Ok(Json(ApiResponse::success(response)))
         }
         Err(e) => {
-            tracing::error!("Failed to get store games: {}", e);
+            tracing::error!("Failed to get store facets: {}", e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
@@ -107,6 +153,8 @@ pub async fn install_game(
     Path(game_id): Path<String>,
     Json(request): Json<InstallGameRequest>,
 ) -> Result<StatusCode, StatusCode> {
+    ensure_visible(&state, &user, &game_id).await?;
+
     match state.db.install_game_for_user(&user.id, &game_id, request.install_path).await {
         Ok(true) => Ok(StatusCode::CREATED),
         Ok(false) => Err(StatusCode::NOT_FOUND), // Game doesn't exist or isn't available
@@ -141,24 +189,34 @@ pub async fn uninstall_game(
 pub async fn get_user_library(
     State(state): State<AppState>,
     Extension(user): Extension<User>,
-    Query(params): Query<PaginationQuery>,
+    Query(params): Query<GameListQuery>,
 ) -> Result<Json<ApiResponse<UserLibraryResponse>>, StatusCode> {
     let page = params.page.unwrap_or(1);
     let per_page = params.per_page.unwrap_or(20);
+    let filter = params.filter();
+
+    let (user_games, total) = match state.db.get_user_library(viewer(&state, &user), &filter, page, per_page).await {
+        Ok(result) => result,
+        Err(e) => {
+            tracing::error!("Failed to get user library: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
 
-    match state.db.get_user_library(&user.id, page, per_page).await {
-        Ok((user_games, total)) => {
+    match state.db.get_game_facets(&filter, GameScope::Library(viewer(&state, &user))).await {
+        Ok(facets) => {
             let games: Vec<UserGameResponse> = user_games.into_iter().map(|ug| ug.into()).collect();
             let response = UserLibraryResponse {
                 games,
                 total,
                 page,
                 per_page,
+                facets,
             };
             Ok(Json(ApiResponse::success(response)))
         }
         Err(e) => {
-            tracing::error!("Failed to get user library: {}", e);
+            tracing::error!("Failed to get library facets: {}", e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
@@ -172,6 +230,8 @@ pub async fn get_user_game(
     Extension(user): Extension<User>,
     Path(game_id): Path<String>,
 ) -> Result<Json<ApiResponse<UserGameResponse>>, StatusCode> {
+    ensure_visible(&state, &user, &game_id).await?;
+
     match state.db.get_user_game(&user.id, &game_id).await {
         Ok(Some(user_game)) => Ok(Json(ApiResponse::success(user_game.into()))),
         Ok(None) => Err(StatusCode::NOT_FOUND),
@@ -181,3 +241,151 @@ pub async fn get_user_game(
         }
     }
 }
+
+// Stream a game's file to the client, supporting resumable range requests
+#[debug_handler]
+pub async fn download_game(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(game_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    // Before anything else, so games the user may not see are indistinguishable from missing ones
+    ensure_visible(&state, &user, &game_id).await?;
+
+    let game = match state.db.get_game_by_id(&game_id).await {
+        Ok(Some(game)) => game,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get game for download: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    if !game.is_available {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let file_path = match game.file_path {
+        Some(file_path) => file_path,
+        None => return Err(StatusCode::NOT_FOUND),
+    };
+
+    // Folder games have no single file to send; clients fetch each manifest entry instead
+    if tokio::fs::metadata(&file_path).await.is_ok_and(|metadata| metadata.is_dir()) {
+        return Ok((
+            StatusCode::CONFLICT,
+            Json(ApiResponse::<()>::error(
+                "This game is a folder; download its files from /files/{relative_path} as listed in the manifest".to_string(),
+            )),
+        ).into_response());
+    }
+
+    downloads::serve_file(std::path::Path::new(&file_path), &headers).await
+}
+
+// Download one file of a game by its manifest path (resumable). Only paths in the manifest
+// are served, so nothing outside the game's folder can be reached.
+#[debug_handler]
+pub async fn download_game_file(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path((game_id, relative_path)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    ensure_visible(&state, &user, &game_id).await?;
+    let game = match state.db.get_game_by_id(&game_id).await {
+        Ok(Some(game)) if game.is_available => game,
+        Ok(_) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get game for file download: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let file_path = game.file_path.ok_or(StatusCode::NOT_FOUND)?;
+
+    match state.db.get_manifest_file(&game_id, &relative_path).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get manifest file: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+    if relative_path.split('/').any(|part| part.is_empty() || part == "." || part == "..") {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    // A single-file game's manifest has one entry, named after the file itself
+    let root = std::path::Path::new(&file_path);
+    let path = match tokio::fs::metadata(root).await {
+        Ok(metadata) if metadata.is_dir() => root.join(&relative_path),
+        Ok(_) if root.file_name().is_some_and(|name| name.to_string_lossy() == relative_path) => root.to_path_buf(),
+        _ => return Err(StatusCode::NOT_FOUND),
+    };
+
+    downloads::serve_file(&path, &headers).await
+}
+
+// Get the file manifest (paths, sizes, SHA-256) used to verify a download
+#[debug_handler]
+#[allow(unused_variables)]
+pub async fn get_game_manifest(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(game_id): Path<String>,
+) -> Result<Json<ApiResponse<ManifestResponse>>, StatusCode> {
+    match state.db.get_game_by_id(&game_id).await {
+        Ok(Some(game)) if game.is_available => {}
+        Ok(_) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get game for manifest: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+    ensure_visible(&state, &user, &game_id).await?;
+
+    let manifest = match state.db.get_manifest(&game_id).await {
+        Ok(Some(manifest)) => manifest,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get manifest: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    match state.db.get_manifest_files(&game_id).await {
+        Ok(files) => Ok(Json(ApiResponse::success(ManifestResponse { manifest, files }))),
+        Err(e) => {
+            tracing::error!("Failed to get manifest files: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// List a game's builds with their release notes, newest first
+#[debug_handler]
+#[allow(unused_variables)]
+pub async fn get_game_builds(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(game_id): Path<String>,
+) -> Result<Json<ApiResponse<Vec<GameBuildResponse>>>, StatusCode> {
+    match state.db.get_game_by_id(&game_id).await {
+        Ok(Some(game)) if game.is_available => {}
+        Ok(_) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get game for builds: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+    ensure_visible(&state, &user, &game_id).await?;
+
+    match state.db.get_game_builds(&game_id).await {
+        Ok(builds) => Ok(Json(ApiResponse::success(builds.into_iter().map(GameBuildResponse::from).collect()))),
+        Err(e) => {
+            tracing::error!("Failed to get game builds: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
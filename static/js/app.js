@@ -356,7 +356,7 @@ class GameHub {
 
     createGameCard(game) {
         const coverImage = game['cover_url']
-            ? `<img src="${game['cover_url']}" alt="${game.name} cover" loading="lazy">`
+            ? `<img src="${this.thumbnailUrl(game['cover_url'], 'medium')}" alt="${game.name} cover" loading="lazy">`
             : `<i class="fas fa-gamepad game-cover-placeholder"></i>`;
 
         // Fixed: Using bracket notation to avoid HTMLTableElement.summary conflict
@@ -678,6 +678,12 @@ class GameHub {
         }
     }
 
+    // Mirrored artwork has pre-sized JPEG thumbnails next to it; remote URLs are used as-is
+    thumbnailUrl(url, size) {
+        if (!url.startsWith('/media/')) return url;
+        return url.replace(/\.[a-z]+$/, `_${size}.jpg`);
+    }
+
     formatFileSize(bytes) {
         if (bytes === 0) return '0 GB';
         const k = 1024;